@@ -5,22 +5,19 @@ fn main() -> eyre::Result<()> {
     if cfg!(target_os = "windows") {
         bail!("does not work on Windows yet");
     }
-    let status = Command::new("ffmpeg")
-        .args(["-version"])
-        .status()
-        .expect("unable to run process");
 
-    if !status.success() {
-        bail!("FFMpeg does not seems to be installed");
-    }
-
-    let status = Command::new("ffprobe")
-        .args(["-version"])
-        .status()
-        .expect("unable to run process");
-
-    if !status.success() {
-        bail!("FFProbe does not seems to be installed");
+    // Only needed at runtime, the library can be built without them
+    for program in ["ffmpeg", "ffprobe"] {
+        let installed = Command::new(program)
+            .arg("-version")
+            .status()
+            .is_ok_and(|status| status.success());
+        if !installed {
+            println!(
+                "cargo:warning={} does not seem to be installed, it is needed at runtime",
+                program
+            );
+        }
     }
 
     Ok(())
@@ -0,0 +1,353 @@
+use crate::{Position, Rational, Timecode};
+
+/// Values that can be interpolated between two keyframes
+pub trait Interpolate: Clone {
+    /// Returns the value at `t` between `self` (0) and `other` (1).
+    /// `t` can be out of `[0, 1]` with easings that overshoot.
+    fn interpolate(&self, other: &Self, t: f64) -> Self;
+}
+
+impl Interpolate for f64 {
+    fn interpolate(&self, other: &Self, t: f64) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Interpolate for f32 {
+    fn interpolate(&self, other: &Self, t: f64) -> Self {
+        (*self as f64).interpolate(&(*other as f64), t) as f32
+    }
+}
+
+macro_rules! interpolate_integer {
+    ($($integer:ty),*) => {
+        $(
+            /// Rounded to the nearest integer, and saturated
+            impl Interpolate for $integer {
+                fn interpolate(&self, other: &Self, t: f64) -> Self {
+                    (*self as f64).interpolate(&(*other as f64), t).round() as $integer
+                }
+            }
+        )*
+    };
+}
+
+interpolate_integer!(u32, i32, u64, i64);
+
+macro_rules! interpolate_tuple {
+    ($($name:ident $index:tt),*) => {
+        impl<$($name: Interpolate),*> Interpolate for ($($name,)*) {
+            fn interpolate(&self, other: &Self, t: f64) -> Self {
+                ($(self.$index.interpolate(&other.$index, t),)*)
+            }
+        }
+    };
+}
+
+interpolate_tuple!(A 0, B 1);
+interpolate_tuple!(A 0, B 1, C 2);
+interpolate_tuple!(A 0, B 1, C 2, D 3);
+
+/// How the value goes from a keyframe to the next one
+#[derive(Debug, Clone, Copy, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+    #[default]
+    Linear,
+    /// The value stays the same until the next keyframe
+    Hold,
+    /// Starts slowly (cubic)
+    EaseIn,
+    /// Ends slowly (cubic)
+    EaseOut,
+    /// Starts and ends slowly (cubic)
+    EaseInOut,
+    /// CSS-like cubic bezier from (0, 0) to (1, 1), with control points
+    /// (x1, y1) and (x2, y2). x1 and x2 must be in `[0, 1]`.
+    CubicBezier(f64, f64, f64, f64),
+    /// Damped spring, oscillating `frequency` times between the keyframes
+    /// around the next value, `damping` being in `(0, 1)` (lower is bouncier)
+    Spring { frequency: f64, damping: f64 },
+}
+
+impl Easing {
+    /// Returns the progress at `t` (in `[0, 1]`), 0 at the start and 1 at
+    /// the end
+    pub fn apply(&self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        match *self {
+            Self::Linear => t,
+            Self::Hold => 0.0,
+            Self::EaseIn => t * t * t,
+            Self::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Self::EaseInOut if t < 0.5 => 4.0 * t * t * t,
+            Self::EaseInOut => 1.0 - (2.0 - 2.0 * t).powi(3) / 2.0,
+            Self::CubicBezier(x1, y1, x2, y2) => cubic_bezier(x1, y1, x2, y2, t),
+            Self::Spring { frequency, damping } => {
+                let spring = |t: f64| {
+                    let damping = damping.clamp(0.001, 0.999);
+                    let omega = 2.0 * std::f64::consts::PI * frequency.max(0.001);
+                    let damped = omega * (1.0 - damping * damping).sqrt();
+                    1.0 - (-damping * omega * t).exp()
+                        * ((damped * t).cos() + damping * omega / damped * (damped * t).sin())
+                };
+                // Compensate for the spring not being fully settled at the end
+                spring(t) + (1.0 - spring(1.0)) * t
+            }
+        }
+    }
+}
+
+/// Returns y for the given x on a cubic bezier from (0, 0) to (1, 1)
+fn cubic_bezier(x1: f64, y1: f64, x2: f64, y2: f64, x: f64) -> f64 {
+    let bezier = |a: f64, b: f64, s: f64| {
+        3.0 * a * s * (1.0 - s).powi(2) + 3.0 * b * s * s * (1.0 - s) + s * s * s
+    };
+
+    // x is monotonic in s when x1 and x2 are in [0, 1], find s by bisection
+    let (mut low, mut high) = (0.0, 1.0);
+    for _ in 0..50 {
+        let s = (low + high) / 2.0;
+        if bezier(x1, x2, s) < x {
+            low = s;
+        } else {
+            high = s;
+        }
+    }
+
+    bezier(y1, y2, (low + high) / 2.0)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Keyframe<T> {
+    frame: u64,
+    value: T,
+    easing: Easing, // from the previous keyframe to this one
+}
+
+/// A value changing over time, through keyframes.
+/// Keyframes are positioned at frames, or with `keyframe_at` at times or
+/// timecodes, which need the frame rate and the start timecode of the clip.
+/// Before the first keyframe the value is the one of the first keyframe, and
+/// after the last one the one of the last keyframe.
+///
+/// A constant converts to an `Animated` value, so that effects taking an
+/// `impl Into<Animated<T>>` also accept plain values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Animated<T> {
+    keyframes: Vec<Keyframe<T>>, // sorted by frame
+    frame_rate: Option<Rational>,
+    start_timecode: Option<Timecode>,
+}
+
+impl<T: Interpolate> Animated<T> {
+    /// Creates a value starting at `value` on the first frame
+    pub fn new(value: T) -> Self {
+        Self {
+            keyframes: vec![Keyframe {
+                frame: 0,
+                value,
+                easing: Easing::Linear,
+            }],
+            frame_rate: None,
+            start_timecode: None,
+        }
+    }
+
+    /// Adds a keyframe on the given frame, the value going from the previous
+    /// keyframe to this one with the given easing
+    pub fn keyframe(mut self, frame: u64, value: T, easing: Easing) -> Self {
+        // After the keyframes on the same frame, which it replaces
+        let index = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.frame <= frame);
+        self.keyframes.insert(
+            index,
+            Keyframe {
+                frame,
+                value,
+                easing,
+            },
+        );
+
+        self
+    }
+
+    /// Adds a keyframe at a frame, a time or a timecode.
+    /// Times need `at_frame_rate` to be called first, and timecodes
+    /// `at_start_timecode`, as they are relative to the start of the clip.
+    pub fn keyframe_at(
+        self,
+        at: impl Into<Position>,
+        value: T,
+        easing: Easing,
+    ) -> eyre::Result<Self> {
+        let frame = match at.into() {
+            Position::Frame(frame) => frame,
+            Position::Time(time) => {
+                let frame_rate = self.frame_rate.ok_or(eyre::eyre!(
+                    "keyframe at {:?} needs the frame rate of the clip",
+                    time
+                ))?;
+                frame_rate.frames_in(time)
+            }
+            Position::Timecode(timecode) => {
+                let (Some(start), Some(frame_rate)) = (self.start_timecode, self.frame_rate) else {
+                    eyre::bail!(
+                        "keyframe at {} needs the start timecode of the clip",
+                        timecode
+                    );
+                };
+                Position::Timecode(timecode)
+                    .to_frame(frame_rate)
+                    .checked_sub(Position::Timecode(start).to_frame(frame_rate))
+                    .ok_or(eyre::eyre!(
+                        "keyframe at {} is before the start of the clip, {}",
+                        timecode,
+                        start
+                    ))?
+            }
+        };
+
+        Ok(self.keyframe(frame, value, easing))
+    }
+
+    /// Sets the frame rate used to find the frames of the keyframes given as
+    /// times
+    pub fn at_frame_rate(mut self, frame_rate: Rational) -> Self {
+        self.frame_rate = Some(frame_rate);
+        self
+    }
+
+    /// Sets the timecode of the first frame, to find the frames of the
+    /// keyframes given as timecodes. Its frame rate is used if none was set.
+    pub fn at_start_timecode(mut self, timecode: Timecode) -> Self {
+        self.start_timecode = Some(timecode);
+        self.frame_rate.get_or_insert(timecode.frame_rate());
+        self
+    }
+
+    /// Returns true if the value never changes
+    pub fn is_constant(&self) -> bool {
+        self.keyframes.len() == 1
+    }
+
+    /// Returns the value on the given frame
+    pub fn value_at(&self, frame: u64) -> T {
+        let next = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.frame <= frame);
+        match (next.checked_sub(1), self.keyframes.get(next)) {
+            (None, _) => self.keyframes[0].value.clone(),
+            (Some(previous), None) => self.keyframes[previous].value.clone(),
+            (Some(previous), Some(to)) => {
+                let from = &self.keyframes[previous];
+                let t = (frame - from.frame) as f64 / (to.frame - from.frame) as f64;
+                from.value.interpolate(&to.value, to.easing.apply(t))
+            }
+        }
+    }
+}
+
+impl<T: Interpolate> From<T> for Animated<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn keyframes() {
+        let value = Animated::new(0.0)
+            .keyframe(10, 100.0, Easing::Linear)
+            .keyframe(20, 50.0, Easing::Hold)
+            .keyframe(30, 0.0, Easing::Linear);
+        assert_eq!(value.value_at(0), 0.0);
+        assert_eq!(value.value_at(5), 50.0);
+        assert_eq!(value.value_at(10), 100.0);
+        // Held until the keyframe at 20
+        assert_eq!(value.value_at(15), 100.0);
+        assert_eq!(value.value_at(20), 50.0);
+        assert_eq!(value.value_at(25), 25.0);
+        assert_eq!(value.value_at(30), 0.0);
+        assert_eq!(value.value_at(1000), 0.0);
+
+        let constant: Animated<u32> = 42.into();
+        assert!(constant.is_constant());
+        assert_eq!(constant.value_at(1000), 42);
+
+        // Times, and tuples
+        let value = Animated::new((0u32, 0u32))
+            .at_frame_rate(Rational::new(25, 1))
+            .keyframe_at(Duration::from_secs(1), (100, 10), Easing::Linear)
+            .unwrap();
+        assert_eq!(value.value_at(5), (20, 2));
+        assert_eq!(value.value_at(25), (100, 10));
+
+        // Keyframes added out of order
+        let value = Animated::new(0.0)
+            .keyframe(20, 0.0, Easing::Linear)
+            .keyframe(10, 100.0, Easing::Linear);
+        assert_eq!(value.value_at(5), 50.0);
+        assert_eq!(value.value_at(15), 50.0);
+    }
+
+    #[test]
+    fn positions() {
+        let rate = Rational::new(25, 1);
+        assert!(Animated::new(0.0)
+            .keyframe_at(Duration::from_secs(1), 1.0, Easing::Linear)
+            .is_err());
+
+        // Timecodes are relative to the start of the clip
+        let start = Timecode::parse("01:00:00:00", rate).unwrap();
+        let timecode = Timecode::parse("01:00:01:00", rate).unwrap();
+        assert!(Animated::new(0.0)
+            .at_frame_rate(rate)
+            .keyframe_at(timecode, 1.0, Easing::Linear)
+            .is_err());
+        let value = Animated::new(0.0)
+            .at_start_timecode(start)
+            .keyframe_at(timecode, 25.0, Easing::Linear)
+            .unwrap();
+        assert_eq!(value.value_at(10), 10.0);
+        assert!(Animated::new(0.0)
+            .at_start_timecode(timecode)
+            .keyframe_at(start, 1.0, Easing::Linear)
+            .is_err());
+    }
+
+    #[test]
+    fn easings() {
+        for easing in [
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+            Easing::CubicBezier(0.25, 0.1, 0.25, 1.0),
+            Easing::Spring {
+                frequency: 2.0,
+                damping: 0.3,
+            },
+        ] {
+            assert!(easing.apply(0.0).abs() < 1e-9, "{:?}", easing);
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-9, "{:?}", easing);
+        }
+        assert_eq!(Easing::Hold.apply(0.99), 0.0);
+        assert!(Easing::EaseIn.apply(0.5) < 0.5);
+        assert!(Easing::EaseOut.apply(0.5) > 0.5);
+        assert!((Easing::EaseInOut.apply(0.5) - 0.5).abs() < 1e-9);
+        // Linear bezier
+        assert!((Easing::CubicBezier(0.0, 0.0, 1.0, 1.0).apply(0.3) - 0.3).abs() < 1e-6);
+        // Springs overshoot
+        let spring = Easing::Spring {
+            frequency: 2.0,
+            damping: 0.2,
+        };
+        assert!((0..100).any(|t| spring.apply(t as f64 / 100.0) > 1.0));
+    }
+}
@@ -1,94 +1,343 @@
-use crate::{ffmpeg, frame::IterFrame};
+use crate::{
+    ffmpeg,
+    frame::{FrameTiming, IterFrame},
+    position::frame_range,
+    sequence::SequencePattern,
+    ClipId, ClipMetadata, Frame, PixelFormat, Position, Rational, RawPixel, StreamSelector,
+    Thumbnails, Timecode, VideoStreamInfo,
+};
 use eyre::eyre;
-use image::Pixel;
-use std::{fmt::Display, marker::PhantomData, path::PathBuf, time::Duration};
-
-// TODO: move this to a separate file ?
-#[derive(Debug, Clone, Default)]
-pub struct TimeDuration {
-    pub hour: u32,
-    pub min: u32,
-    pub sec: u32,
-}
-
-impl TimeDuration {
-    pub fn new(hour: u32, min: u32, sec: u32) -> Self {
-        Self { hour, min, sec }
-    }
-}
-
-impl Display for TimeDuration {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:02}:{:02}:{:02}", self.hour, self.min, self.sec)
-    }
-}
+use std::{io::Read, marker::PhantomData, path::PathBuf, sync::Arc, time::Duration};
 
 #[derive(Debug, Clone)]
 pub struct Clip<P> {
-    path: PathBuf,
+    id: ClipId,
+    path: PathBuf, // path of the file, or pattern of the image sequence
+    input: ffmpeg::Input,
+    stream: Option<u32>,             // index of the video stream in the file
+    data: Option<ffmpeg::InputData>, // content of the file, when not read from disk
     // Clip informations
-    // TODO: create a ClipMetadata to encapsulate everything
-    infos: ffmpeg::FFMpegInfos,
-    duration: Duration,
-    start: TimeDuration, // used to generate subclips
-    max_nb_frames: u32,  // number of frames to read before stopping
+    metadata: ClipMetadata,
+    duration: Duration, // duration of the clip, not of the whole file
+    start_frame: u64,   // index in the file of the first frame, for subclips
+    max_nb_frames: u32, // number of frames to read before stopping
     dimensions: (u32, u32),
-    fps: f32,
-    pixel_depth: u8,
+    frame_rate: Rational,
+    variable_frame_rate: bool,
+    time_base: Rational,
+    start_pts: i64, // timestamp of the first frame of the file
+    pixel_format: PixelFormat,
+    alpha_decoder: Option<&'static str>,
+    timecode: Option<Timecode>, // timecode of the first frame of the file
     nb_frames: usize,
     _phantom: PhantomData<P>,
 }
 
 impl<P> Clip<P> {
     /// Create a new clip
-    fn new(
-        path: impl Into<PathBuf>,
-        start: Option<TimeDuration>,
-        end: Option<TimeDuration>,
-    ) -> eyre::Result<Self> {
+    fn new(path: impl Into<PathBuf>, selector: StreamSelector) -> eyre::Result<Self> {
         let _from_file = move |path: PathBuf| -> eyre::Result<Self> {
+            if is_named_pipe(&path) {
+                let pipe = std::fs::File::open(&path)
+                    .map_err(|err| eyre!("cannot open {:?}: {}", path, err))?;
+                return Self::from_stream(Box::new(pipe), selector);
+            }
             if !path.is_file() {
                 eyre::bail!("file not found");
             }
 
-            let infos = ffmpeg::FFMpegInfos::from_file(&path)?;
-            let dimensions = infos
-                .dimensions()
-                .ok_or(eyre!("no video dimensions found"))?;
-            let duration =
-                Duration::from_secs_f32(infos.duration().ok_or(eyre!("no video duration found"))?);
-            let (_, pixel_depth) = infos.pixel().ok_or(eyre!("no pixel format found"))?;
-            let fps = infos.fps().ok_or(eyre!("no video fps found"))?;
-            let nb_frames = infos.nb_frames().ok_or(eyre!("no video nb frames found"))?;
-            let start = start.unwrap_or_default();
-            let max_nb_frames = end
-                .map(|end| {
-                    let secs = end.hour * 3600 + end.min * 60 + end.sec;
-                    let nb_frames = secs as f32 * fps;
-                    nb_frames as u32
-                })
-                .unwrap_or(nb_frames as u32);
-
-            Ok(Self {
-                path,
-                infos,
-                start,
-                max_nb_frames,
-                duration,
-                dimensions,
-                fps,
-                pixel_depth,
-                nb_frames,
-                _phantom: PhantomData,
-            })
+            let mut infos = ffmpeg::FFMpegInfos::from_file(&path)?;
+            infos.select_video_stream(selector)?;
+            Self::from_infos(path, infos)
         };
 
         _from_file(path.into())
     }
 
+    /// Create a new clip from the content of a file
+    fn from_memory(data: Arc<[u8]>, selector: StreamSelector) -> eyre::Result<Self> {
+        let mut infos = ffmpeg::FFMpegInfos::from_bytes(&data)?;
+        infos.select_video_stream(selector)?;
+
+        Ok(Self {
+            data: Some(ffmpeg::InputData::Memory(data)),
+            ..Self::from_infos(PathBuf::from("pipe:0"), infos)?
+        })
+    }
+
+    /// Create a new clip read once from a stream, probed from its first bytes
+    fn from_stream(
+        mut reader: Box<dyn Read + Send>,
+        selector: StreamSelector,
+    ) -> eyre::Result<Self> {
+        let mut head = Vec::new();
+        reader
+            .by_ref()
+            .take(PROBE_SIZE)
+            .read_to_end(&mut head)
+            .map_err(|err| eyre!("cannot read the video: {}", err))?;
+        let mut infos = ffmpeg::FFMpegInfos::from_bytes(&head)?;
+        infos.select_video_stream(selector)?;
+
+        Ok(Self {
+            data: Some(ffmpeg::InputData::stream(head, reader)),
+            ..Self::from_infos(PathBuf::from("pipe:0"), infos)?
+        })
+    }
+
+    /// Create a new clip spanning the whole file, from what ffprobe reported
+    fn from_infos(path: PathBuf, infos: ffmpeg::FFMpegInfos) -> eyre::Result<Self> {
+        let duration =
+            Duration::from_secs_f64(infos.duration().ok_or(eyre!("no video duration found"))?);
+        let frame_rate = infos
+            .frame_rate()
+            .ok_or(eyre!("no video frame rate found"))?;
+        let nb_frames = infos.nb_frames().ok_or(eyre!("no video nb frames found"))?;
+
+        Self::from_parts(
+            path,
+            infos,
+            ffmpeg::Input::File,
+            frame_rate,
+            duration,
+            nb_frames,
+        )
+    }
+
+    /// Create a new clip from what ffprobe reported about its images, with
+    /// the given timing
+    fn from_parts(
+        path: PathBuf,
+        infos: ffmpeg::FFMpegInfos,
+        input: ffmpeg::Input,
+        frame_rate: Rational,
+        duration: Duration,
+        nb_frames: usize,
+    ) -> eyre::Result<Self> {
+        let dimensions = infos
+            .dimensions()
+            .ok_or(eyre!("no video dimensions found"))?;
+        let pixel_format = infos.pixel_format().ok_or(eyre!("no pixel format found"))?;
+        // Images are timed by the frame rate given to FFMpeg
+        let (time_base, start_pts) = match input {
+            ffmpeg::Input::File => (
+                infos.time_base().unwrap_or(frame_rate.recip()),
+                infos.start_pts().unwrap_or(0),
+            ),
+            _ => (frame_rate.recip(), 0),
+        };
+
+        Ok(Self {
+            id: ClipId::next(),
+            path,
+            input,
+            stream: match input {
+                ffmpeg::Input::File => infos.video_index(),
+                _ => None,
+            },
+            data: None,
+            metadata: infos.metadata(),
+            start_frame: 0,
+            max_nb_frames: nb_frames as u32,
+            duration,
+            dimensions,
+            frame_rate,
+            variable_frame_rate: input == ffmpeg::Input::File && infos.is_variable_frame_rate(),
+            time_base,
+            start_pts,
+            pixel_format,
+            alpha_decoder: infos.alpha_decoder(),
+            timecode: infos.timecode(),
+            nb_frames,
+            _phantom: PhantomData,
+        })
+    }
+
     /// Creates a new clip from a file.
+    /// The video stream read is the first one that is not a cover art, use
+    /// `from_file_stream` to choose another one.
+    /// Named pipes can only be read once, as with `from_reader`.
     pub fn from_file(path: impl Into<PathBuf>) -> eyre::Result<Self> {
-        Self::new(path, None, None)
+        Self::new(path, StreamSelector::Default)
+    }
+
+    /// Creates a new clip from a video stream of a file, ie an angle of a
+    /// multi-angle video
+    pub fn from_file_stream(
+        path: impl Into<PathBuf>,
+        selector: StreamSelector,
+    ) -> eyre::Result<Self> {
+        Self::new(path, selector)
+    }
+
+    /// Creates a new clip from the content of a video file, ie an upload,
+    /// without writing it to disk.
+    /// The content is written to the input of FFMpeg each time frames are
+    /// read. As pipes cannot be seeked, reading from the middle of the clip
+    /// decodes every frame before, and MP4 files must have their index at the
+    /// start (`-movflags faststart`).
+    pub fn from_bytes(data: impl Into<Arc<[u8]>>) -> eyre::Result<Self> {
+        Self::from_memory(data.into(), StreamSelector::Default)
+    }
+
+    /// Creates a new clip from a video read from `reader`, ie stdin.
+    /// The video is not kept in memory: its first bytes are probed, then the
+    /// whole stream is copied to FFMpeg, from a thread, as frames are
+    /// decoded. The clip can thus only be read once, reading its frames again,
+    /// even from a clone or a subclip, fails. Use `from_reader_buffered` for
+    /// random access.
+    pub fn from_reader(reader: impl Read + Send + 'static) -> eyre::Result<Self> {
+        Self::from_stream(Box::new(reader), StreamSelector::Default)
+    }
+
+    /// Creates a new clip from a video read from `reader`, which is read
+    /// into memory first so that frames can be read several times, see
+    /// `from_bytes`
+    pub fn from_reader_buffered(reader: impl Read) -> eyre::Result<Self> {
+        Self::from_bytes(read_all(reader)?)
+    }
+
+    /// Creates a new clip from a numbered image sequence, ie
+    /// `renders/frame_%05d.png`, played at the given frame rate.
+    /// As with FFMpeg, the sequence starts at the lowest number found and stops
+    /// at the first missing image. The metadata are the ones of the first
+    /// image.
+    pub fn from_image_sequence(
+        pattern: impl Into<PathBuf>,
+        frame_rate: Rational,
+    ) -> eyre::Result<Self> {
+        if frame_rate.num() <= 0 {
+            eyre::bail!("invalid frame rate {}", frame_rate);
+        }
+
+        let pattern = pattern.into();
+        let sequence = SequencePattern::parse(&pattern)?;
+        let (start_number, nb_frames) = sequence.find_images()?;
+        let infos = ffmpeg::FFMpegInfos::from_file(&sequence.path(start_number))?;
+
+        Self::from_parts(
+            pattern,
+            infos,
+            ffmpeg::Input::ImageSequence { start_number },
+            frame_rate,
+            frame_rate.frame_time(nb_frames as u64),
+            nb_frames,
+        )
+    }
+
+    /// Creates a new clip showing a still image for the given duration, ie a
+    /// title card.
+    pub fn from_still(
+        path: impl Into<PathBuf>,
+        duration: Duration,
+        frame_rate: Rational,
+    ) -> eyre::Result<Self> {
+        if frame_rate.num() <= 0 {
+            eyre::bail!("invalid frame rate {}", frame_rate);
+        }
+        let nb_frames = frame_rate.frames_in(duration);
+        if nb_frames == 0 {
+            eyre::bail!("still duration {:?} is shorter than a frame", duration);
+        }
+
+        let path = path.into();
+        let infos = ffmpeg::FFMpegInfos::from_file(&path)?;
+
+        Self::from_parts(
+            path,
+            infos,
+            ffmpeg::Input::Still,
+            frame_rate,
+            frame_rate.frame_time(nb_frames),
+            nb_frames as usize,
+        )
+    }
+
+    /// Returns the id of the clip, shared with its subclips
+    pub fn id(&self) -> ClipId {
+        self.id
+    }
+
+    /// Returns the metadata of the underlying file
+    pub fn metadata(&self) -> &ClipMetadata {
+        &self.metadata
+    }
+
+    /// Returns the metadata of the video stream read, None for images
+    pub fn video_stream(&self) -> Option<&VideoStreamInfo> {
+        let index = self.stream?;
+
+        self.metadata
+            .video_streams
+            .iter()
+            .find(|video| video.index == index)
+    }
+
+    /// Returns the (width, height) of the clip
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.dimensions
+    }
+
+    /// Returns the frame rate of the clip.
+    /// For variable frame rate clips, this is the average frame rate, and the
+    /// frames are read as if the clip had a constant frame rate.
+    pub fn frame_rate(&self) -> Rational {
+        self.frame_rate
+    }
+
+    /// Returns the number of frames per second, as a floating point number.
+    /// Prefer `frame_rate` when precision matters (ie 30000/1001).
+    pub fn fps(&self) -> f64 {
+        self.frame_rate.as_f64()
+    }
+
+    /// Returns true if the source has a variable frame rate
+    pub fn is_variable_frame_rate(&self) -> bool {
+        self.variable_frame_rate
+    }
+
+    /// Returns the duration of the clip
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Returns the number of frames of the whole file
+    pub fn nb_frames(&self) -> usize {
+        self.nb_frames
+    }
+
+    /// Returns the pixel format of the source.
+    /// Frames can be read in any `RawPixel` type whatever the source is, use
+    /// `Rgb<u16>`/`Rgba<u16>` for high bit depth sources, and `Rgba` to keep
+    /// the alpha channel.
+    pub fn pixel_format(&self) -> &PixelFormat {
+        &self.pixel_format
+    }
+
+    /// Returns true if the source has an alpha channel
+    pub fn has_alpha(&self) -> bool {
+        self.pixel_format.has_alpha() || self.alpha_decoder.is_some()
+    }
+
+    /// Returns the number of frames that will be read by `iter_frames`
+    pub fn len(&self) -> usize {
+        self.max_nb_frames as usize
+    }
+
+    /// Returns true if there is no frame to read
+    pub fn is_empty(&self) -> bool {
+        self.max_nb_frames == 0
+    }
+
+    /// Returns the index, in the source, of the first frame of the clip
+    pub fn start_frame(&self) -> u64 {
+        self.start_frame
+    }
+
+    /// Returns the timecode of the first frame of the clip, if the file has
+    /// one
+    pub fn start_timecode(&self) -> Option<Timecode> {
+        self.timecode.map(|timecode| timecode + self.start_frame)
     }
 
     /// Iter on all the frames of the video.
@@ -97,53 +346,374 @@ impl<P> Clip<P> {
     /// We pass `max_nb_frames` to `IterFrame` just for the iterator's size hint.
     pub fn iter_frames(self) -> eyre::Result<IterFrame<P>>
     where
-        P: Pixel,
+        P: RawPixel,
     {
-        let reader = ffmpeg::FFMpegVideoReader::from_file(
-            &self.path,
-            self.dimensions,
-            self.pixel_depth,
-            self.start.to_string(),
-            self.max_nb_frames,
-        )?;
+        let reader = ffmpeg::FFMpegVideoReader::from_file::<P>(&self.path, &self.read_options())?;
 
         Ok(IterFrame::new(
             reader,
             self.dimensions,
             self.max_nb_frames as usize,
+            self.timing(self.start_frame),
         ))
     }
 
-    /// Create a subclip from the current clip
-    pub fn subclip(&self, start: TimeDuration, during: TimeDuration) -> eyre::Result<Self> {
-        Self::new(self.path.clone(), Some(start), Some(during))
+    /// Returns the frame at the given position, relative to the start of the
+    /// clip (see `subclip_for` for how positions are interpreted).
+    /// Only this frame is output by FFMpeg, which makes it suited to random
+    /// access.
+    pub fn frame_at(&self, position: impl Into<Position>) -> eyre::Result<Frame<P>>
+    where
+        P: RawPixel,
+    {
+        let frame = self.resolve(position.into())?.to_frame(self.frame_rate);
+        if frame >= self.max_nb_frames as u64 {
+            eyre::bail!(
+                "frame {} is out of the clip range 0..{}",
+                frame,
+                self.max_nb_frames
+            );
+        }
+
+        let mut frames = self.with_range(frame, frame + 1)?.iter_frames()?;
+        frames.next().ok_or_else(|| {
+            frames
+                .read_error()
+                .take()
+                .unwrap_or_else(|| eyre!("cannot read frame {}", frame))
+        })
+    }
+
+    /// Returns the frame at the given index, relative to the start of the clip
+    pub fn frame_at_index(&self, index: u64) -> eyre::Result<Frame<P>>
+    where
+        P: RawPixel,
+    {
+        self.frame_at(index)
+    }
+
+    /// Returns thumbnails of the clip, with the time at which each one was
+    /// requested.
+    /// FFMpeg seeks to the keyframe before each time and outputs it, without
+    /// decoding the frames in between: this is fast, but thumbnails are not
+    /// frame accurate, use `frame_at` for that. For the same reason, their
+    /// infos are the ones of the requested frames.
+    /// Combine with `with_dimensions` to have FFMpeg scale them down.
+    pub fn thumbnails(
+        &self,
+        spacing: impl Into<Thumbnails>,
+    ) -> eyre::Result<Vec<(Duration, Frame<P>)>>
+    where
+        P: RawPixel,
+    {
+        spacing
+            .into()
+            .times(self.duration)?
+            .into_iter()
+            .map(|time| {
+                let frame = self
+                    .frame_rate
+                    .frames_in(time)
+                    .min(self.max_nb_frames.saturating_sub(1) as u64);
+                let options = ffmpeg::ReadOptions {
+                    start_frame: self.start_frame + frame,
+                    max_nb_frames: 1,
+                    accurate_seek: false,
+                    ..self.read_options()
+                };
+                let reader = ffmpeg::FFMpegVideoReader::from_file::<P>(&self.path, &options)?;
+
+                let mut frames =
+                    IterFrame::new(reader, self.dimensions, 1, self.timing(options.start_frame));
+                let thumbnail = frames.next().ok_or_else(|| {
+                    frames
+                        .read_error()
+                        .take()
+                        .unwrap_or_else(|| eyre!("cannot read thumbnail at {:?}", time))
+                })?;
+
+                Ok((time, thumbnail))
+            })
+            .collect()
+    }
+
+    /// Returns the same clip, with frames scaled to the given dimensions by
+    /// FFMpeg while decoding.
+    /// This is cheaper than resizing the frames afterwards.
+    pub fn with_dimensions(self, dimensions: (u32, u32)) -> Self {
+        Self { dimensions, ..self }
+    }
+
+    /// Returns the same clip, read as another pixel type
+    pub(crate) fn cast<O>(&self) -> Clip<O> {
+        Clip {
+            id: self.id,
+            path: self.path.clone(),
+            input: self.input,
+            stream: self.stream,
+            data: self.data.clone(),
+            metadata: self.metadata.clone(),
+            duration: self.duration,
+            start_frame: self.start_frame,
+            max_nb_frames: self.max_nb_frames,
+            dimensions: self.dimensions,
+            frame_rate: self.frame_rate,
+            variable_frame_rate: self.variable_frame_rate,
+            time_base: self.time_base,
+            start_pts: self.start_pts,
+            pixel_format: self.pixel_format.clone(),
+            alpha_decoder: self.alpha_decoder,
+            timecode: self.timecode,
+            nb_frames: self.nb_frames,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Returns the timing of frames read from the given index of the file.
+    /// Variable frame rate sources are read at a constant frame rate, the
+    /// timestamps are the ones of the frames as output.
+    fn timing(&self, start_frame: u64) -> FrameTiming {
+        FrameTiming {
+            start_frame,
+            frame_rate: self.frame_rate,
+            time_base: self.time_base,
+            start_pts: self.start_pts,
+            source: Some(self.id),
+        }
+    }
+
+    /// Returns the options to read the frames of the clip
+    fn read_options(&self) -> ffmpeg::ReadOptions<'static> {
+        ffmpeg::ReadOptions {
+            input: self.input,
+            dimensions: self.dimensions,
+            decoder: self.alpha_decoder,
+            stream: self.stream,
+            data: self.data.clone(),
+            start_frame: self.start_frame,
+            frame_rate: self.frame_rate,
+            variable_frame_rate: self.variable_frame_rate,
+            max_nb_frames: self.max_nb_frames,
+            accurate_seek: true,
+        }
+    }
+
+    /// Create a subclip from the current clip, starting at `start` and
+    /// lasting `during`.
+    /// Same as `subclip_for`.
+    pub fn subclip(
+        &self,
+        start: impl Into<Position>,
+        during: impl Into<Position>,
+    ) -> eyre::Result<Self> {
+        self.subclip_for(start, during)
+    }
+
+    /// Create a subclip from the current clip, starting at `start` and
+    /// lasting `duration`.
+    /// Positions can be given as frames (`u64`), `Duration` or `Timecode`, and
+    /// are relative to the start of the current clip, except for a `start`
+    /// timecode which is relative to the timecode of the file if it has one,
+    /// so that edit lists line up.
+    /// The subclip always contains whole frames: the frame displayed at
+    /// `start` is the first one.
+    pub fn subclip_for(
+        &self,
+        start: impl Into<Position>,
+        duration: impl Into<Position>,
+    ) -> eyre::Result<Self> {
+        let (start, end) = frame_range(
+            self.resolve(start.into())?,
+            duration.into(),
+            self.frame_rate,
+        );
+
+        self.with_range(start, end)
+    }
+
+    /// Create a subclip from the current clip, from `start` to `end`
+    /// (excluded). The frame displayed at `end` is not part of the subclip,
+    /// so that `subclip_range(a, b)` and `subclip_range(b, c)` are contiguous.
+    /// See `subclip_for` for how positions are interpreted.
+    pub fn subclip_range(
+        &self,
+        start: impl Into<Position>,
+        end: impl Into<Position>,
+    ) -> eyre::Result<Self> {
+        let start = self.resolve(start.into())?.to_frame(self.frame_rate);
+        let end = self.resolve(end.into())?.to_frame(self.frame_rate);
+        if end < start {
+            eyre::bail!(
+                "subclip ends (frame {}) before it starts (frame {})",
+                end,
+                start
+            );
+        }
+
+        self.with_range(start, end)
+    }
+
+    /// Returns the position relative to the start of the clip.
+    /// Timecodes are converted to frames, as they are absolute.
+    fn resolve(&self, position: Position) -> eyre::Result<Position> {
+        let Position::Timecode(timecode) = position else {
+            return Ok(position);
+        };
+
+        let frame = position.to_frame(self.frame_rate);
+        let origin = self
+            .start_timecode()
+            .map(|origin| Position::Timecode(origin).to_frame(self.frame_rate))
+            .unwrap_or(self.start_frame);
+        if frame < origin {
+            eyre::bail!("timecode {} is before the start of the clip", timecode);
+        }
+
+        Ok(Position::Frame(frame - origin))
+    }
+
+    /// Create a subclip for the frames `[start, end)`, relative to the start of
+    /// the current clip
+    fn with_range(&self, start: u64, end: u64) -> eyre::Result<Self> {
+        let len = self.max_nb_frames as u64;
+        if start > len || end > len {
+            eyre::bail!(
+                "subclip frames {}..{} are out of the clip range 0..{} ({:?} long)",
+                start,
+                end,
+                len,
+                self.duration
+            );
+        }
+
+        Ok(Self {
+            id: self.id,
+            path: self.path.clone(),
+            input: self.input,
+            stream: self.stream,
+            data: self.data.clone(),
+            metadata: self.metadata.clone(),
+            duration: self.frame_rate.frame_time(end - start),
+            start_frame: self.start_frame + start,
+            max_nb_frames: (end - start) as u32,
+            dimensions: self.dimensions,
+            frame_rate: self.frame_rate,
+            variable_frame_rate: self.variable_frame_rate,
+            time_base: self.time_base,
+            start_pts: self.start_pts,
+            pixel_format: self.pixel_format.clone(),
+            alpha_decoder: self.alpha_decoder,
+            timecode: self.timecode,
+            nb_frames: self.nb_frames,
+            _phantom: PhantomData,
+        })
     }
 }
 
+/// Number of bytes of a stream given to ffprobe, as FFMpeg's default
+/// `probesize`
+const PROBE_SIZE: u64 = 5_000_000;
+
+/// Reads a source until its end
+fn read_all(mut reader: impl Read) -> eyre::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    reader
+        .read_to_end(&mut data)
+        .map_err(|err| eyre!("cannot read the video: {}", err))?;
+
+    Ok(data)
+}
+
+/// Named pipes are read as streams, as probing the file would consume its
+/// first bytes
+#[cfg(unix)]
+fn is_named_pipe(path: &std::path::Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    std::fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_fifo())
+}
+
+#[cfg(not(unix))]
+fn is_named_pipe(_path: &std::path::Path) -> bool {
+    false
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{ffmpeg::FFMpegVideoWriter, EffectsExt};
-    use std::path::Path;
+    use image::Rgb;
+
+    fn fixture_clip(json: &str) -> Clip<Rgb<u8>> {
+        let infos = ffmpeg::FFMpegInfos::from_json(json).unwrap();
+        Clip::from_infos(PathBuf::from("fixture"), infos).unwrap()
+    }
 
     #[test]
-    fn test() {
-        let clip = Clip::from_file("/home/zllak/Downloads/newtest.mp4").unwrap();
-        let out_path = Path::new("/tmp/out.mp4");
-        let mut out =
-            FFMpegVideoWriter::to_file(&out_path.into(), (1080, 1920), clip.fps, "gray").unwrap();
-
-        for frame in clip
-            .subclip(TimeDuration::new(00, 00, 20), TimeDuration::new(00, 00, 2))
-            .unwrap()
-            .iter_frames()
+    fn subclip_ranges() {
+        let clip = fixture_clip(include_str!("../tests/fixtures/ffprobe/mp4_h264_aac.json"));
+        assert_eq!(clip.len(), 300);
+
+        let subclip = clip.subclip_range(10, 20).unwrap();
+        assert_eq!((subclip.start_frame(), subclip.len()), (10, 10));
+        assert_eq!(subclip.duration(), Duration::from_nanos(333_666_667));
+
+        // Nested subclips are relative to their parent
+        let nested = subclip.subclip_range(2, 5).unwrap();
+        assert_eq!((nested.start_frame(), nested.len()), (12, 3));
+        let nested = clip
+            .subclip_for(Duration::from_secs(5), Duration::from_secs(3))
             .unwrap()
-            .crop(0, 0, 405, 720)
-            .resize(1080, 1920)
-            .grayscale()
-        {
-            out.write_frame(frame.as_raw()).unwrap();
-        }
-        //println!("COUNTED {:?} FRAMES in {:?}", count, now.elapsed());
+            .subclip_for(Duration::from_secs(1), Duration::from_secs(1))
+            .unwrap();
+        assert_eq!((nested.start_frame(), nested.len()), (149 + 29, 30));
+
+        // Out of range
+        assert!(clip.subclip_for(290, 20).is_err());
+        assert!(clip
+            .subclip_range(Duration::from_secs(9), Duration::from_secs(11))
+            .is_err());
+        assert!(subclip.subclip_range(0, 11).is_err());
+        assert!(clip.subclip_range(20, 10).is_err());
+        assert!(clip.subclip_range(290, 300).is_ok());
+    }
+
+    #[test]
+    fn frame_timing() {
+        let clip = fixture_clip(include_str!("../tests/fixtures/ffprobe/mp4_h264_aac.json"));
+        let subclip = clip.subclip_range(10, 20).unwrap();
+        assert_eq!(subclip.id(), clip.id());
+        assert_ne!(
+            clip.id(),
+            fixture_clip(include_str!("../tests/fixtures/ffprobe/mp4_h264_aac.json")).id()
+        );
+
+        let info = subclip.timing(subclip.start_frame()).info(2);
+        assert_eq!((info.index, info.pts, info.duration), (12, 12 * 1001, 1001));
+        assert_eq!(info.time_base, Rational::new(1, 30000));
+        assert_eq!(info.time(), Duration::from_nanos(400_400_000));
+        assert_eq!(info.duration_time(), Duration::from_nanos(33_366_667));
+        assert_eq!(info.source, Some(clip.id()));
+    }
+
+    #[test]
+    fn subclip_timecodes() {
+        let clip = fixture_clip(include_str!(
+            "../tests/fixtures/ffprobe/mov_prores4444_timecode.json"
+        ));
+        let rate = clip.frame_rate();
+        let timecode = |timecode| Timecode::parse(timecode, rate).unwrap();
+        assert_eq!(clip.start_timecode(), Some(timecode("01:00:00:00")));
+
+        let subclip = clip
+            .subclip_range(timecode("01:00:01:00"), timecode("01:00:01:12"))
+            .unwrap();
+        assert_eq!((subclip.start_frame(), subclip.len()), (24, 12));
+        assert_eq!(subclip.start_timecode(), Some(timecode("01:00:01:00")));
+
+        // Timecodes stay absolute in nested subclips
+        let nested = subclip.subclip_for(timecode("01:00:01:06"), 3).unwrap();
+        assert_eq!((nested.start_frame(), nested.len()), (30, 3));
+        assert!(subclip.subclip_for(timecode("01:00:00:23"), 1).is_err());
+        assert!(clip.subclip_for(timecode("00:59:59:00"), 1).is_err());
     }
 }
@@ -1,45 +1,40 @@
-use crate::Frame;
+use crate::{effects::FrameContext, Animated, Effect, Frame};
 use image::Pixel;
 
-pub struct Crop<I> {
-    iter: I,
-    x: u32,
-    y: u32,
-    width: u32,
-    height: u32,
+/// Crops the frames at (x, y) to a new (width, height)
+#[derive(Debug, Clone)]
+pub struct Crop {
+    x: Animated<u32>,
+    y: Animated<u32>,
+    width: Animated<u32>,
+    height: Animated<u32>,
 }
 
-impl<I> Crop<I> {
-    pub(in crate::effects) fn new(iter: I, x: u32, y: u32, width: u32, height: u32) -> Self {
+impl Crop {
+    pub fn new(
+        x: impl Into<Animated<u32>>,
+        y: impl Into<Animated<u32>>,
+        width: impl Into<Animated<u32>>,
+        height: impl Into<Animated<u32>>,
+    ) -> Self {
         Self {
-            iter,
-            x,
-            y,
-            width,
-            height,
+            x: x.into(),
+            y: y.into(),
+            width: width.into(),
+            height: height.into(),
         }
     }
 }
 
-impl<I, P> Iterator for Crop<I>
-where
-    P: Pixel + 'static,
-    I: Iterator<Item = Frame<P>>,
-{
-    type Item = I::Item;
+impl<P: Pixel> Effect<P> for Crop {
+    fn apply(&mut self, frame: Frame<P>, context: &FrameContext) -> Frame<P> {
+        let index = context.index;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.iter.next().map(|frame| {
-            let new_image =
-                image::imageops::crop_imm(frame.image(), self.x, self.y, self.width, self.height)
-                    .to_image();
-            let dimensions = new_image.dimensions();
-
-            Frame::from_vec(new_image.into_raw(), dimensions)
-        })
-    }
-
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        self.iter.size_hint()
+        frame.crop(
+            self.x.value_at(index),
+            self.y.value_at(index),
+            self.width.value_at(index),
+            self.height.value_at(index),
+        )
     }
 }
@@ -0,0 +1,168 @@
+use crate::Frame;
+use image::Pixel;
+use std::marker::PhantomData;
+
+/// Where a frame is in the stream an effect is applied to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameContext {
+    /// Index of the frame in the stream, from 0, to animate parameters
+    pub index: u64,
+    /// Number of frames of the stream, if known
+    pub nb_frames: Option<u64>,
+}
+
+/// An effect applied to each frame of a stream, turning `Frame<P>` into
+/// `Frame<O>`.
+/// Effects can keep a state between frames, ie to blend them. Boxed effects
+/// are effects too, and a `Vec` of effects applies them in order, so that
+/// chains can be built at runtime.
+pub trait Effect<P: Pixel, O: Pixel = P> {
+    /// Applies the effect to the frame
+    fn apply(&mut self, frame: Frame<P>, context: &FrameContext) -> Frame<O>;
+
+    /// Returns the frames still held by the effect once the stream ended,
+    /// for effects that delay frames. `context` follows the last frame.
+    fn flush(&mut self, _context: &FrameContext) -> Vec<Frame<O>> {
+        Vec::new()
+    }
+
+    /// Returns how many frames `flush` returns at most, `None` if unknown.
+    /// This is added to the number of frames of the stream.
+    fn extra_frames(&self) -> Option<usize> {
+        Some(0)
+    }
+}
+
+impl<P, O, E> Effect<P, O> for Box<E>
+where
+    P: Pixel,
+    O: Pixel,
+    E: Effect<P, O> + ?Sized,
+{
+    fn apply(&mut self, frame: Frame<P>, context: &FrameContext) -> Frame<O> {
+        (**self).apply(frame, context)
+    }
+
+    fn flush(&mut self, context: &FrameContext) -> Vec<Frame<O>> {
+        (**self).flush(context)
+    }
+
+    fn extra_frames(&self) -> Option<usize> {
+        (**self).extra_frames()
+    }
+}
+
+impl<P, E> Effect<P> for Vec<E>
+where
+    P: Pixel,
+    E: Effect<P>,
+{
+    fn apply(&mut self, frame: Frame<P>, context: &FrameContext) -> Frame<P> {
+        self.iter_mut()
+            .fold(frame, |frame, effect| effect.apply(frame, context))
+    }
+
+    fn flush(&mut self, context: &FrameContext) -> Vec<Frame<P>> {
+        let mut frames = Vec::new();
+        for effect in self.iter_mut() {
+            // Frames flushed by the previous effects go through this one
+            let mut output = frames
+                .into_iter()
+                .enumerate()
+                .map(|(offset, frame)| {
+                    let context = FrameContext {
+                        index: context.index + offset as u64,
+                        ..*context
+                    };
+                    effect.apply(frame, &context)
+                })
+                .collect::<Vec<_>>();
+            output.extend(effect.flush(&FrameContext {
+                index: context.index + output.len() as u64,
+                ..*context
+            }));
+            frames = output;
+        }
+
+        frames
+    }
+
+    fn extra_frames(&self) -> Option<usize> {
+        self.iter().try_fold(0usize, |extra, effect| {
+            extra.checked_add(effect.extra_frames()?)
+        })
+    }
+}
+
+/// Applies an effect to the frames of an iterator
+pub struct Apply<I, E, O>
+where
+    O: Pixel,
+{
+    iter: I,
+    effect: E,
+    index: u64,
+    nb_frames: Option<u64>,
+    flushed: Option<std::vec::IntoIter<Frame<O>>>, // once the stream ended
+    _phantom: PhantomData<fn() -> O>,
+}
+
+impl<I: Iterator, E, O: Pixel> Apply<I, E, O> {
+    pub(in crate::effects) fn new(iter: I, effect: E) -> Self {
+        let nb_frames = iter.size_hint().1.map(|upper| upper as u64);
+
+        Self {
+            iter,
+            effect,
+            index: 0,
+            nb_frames,
+            flushed: None,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<I, E, P, O> Iterator for Apply<I, E, O>
+where
+    P: Pixel,
+    O: Pixel,
+    I: Iterator<Item = Frame<P>>,
+    E: Effect<P, O>,
+{
+    type Item = Frame<O>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let context = FrameContext {
+            index: self.index,
+            nb_frames: self.nb_frames,
+        };
+
+        if self.flushed.is_none() {
+            if let Some(frame) = self.iter.next() {
+                self.index += 1;
+                return Some(self.effect.apply(frame, &context));
+            }
+            self.flushed = Some(self.effect.flush(&context).into_iter());
+        }
+
+        self.flushed.as_mut()?.next()
+    }
+
+    /// Frames the effect may flush are only in the upper bound until the
+    /// stream ended
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.flushed {
+            Some(flushed) => flushed.size_hint(),
+            None => {
+                let (lower, upper) = self.iter.size_hint();
+                let extra = self.effect.extra_frames();
+                (
+                    lower,
+                    upper
+                        .zip(extra)
+                        .and_then(|(upper, extra)| upper.checked_add(extra)),
+                )
+            }
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use crate::{
+    effects::{resize::Resampler, FrameContext},
+    Animated, Easing, Effect, Frame, Interpolate,
+};
+use image::{imageops::FilterType, ImageBuffer, Pixel};
+
+/// A rectangle with subpixel coordinates, in pixels
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+impl Interpolate for Rect {
+    fn interpolate(&self, other: &Self, t: f64) -> Self {
+        Self::new(
+            self.x.interpolate(&other.x, t),
+            self.y.interpolate(&other.y, t),
+            self.width.interpolate(&other.width, t),
+            self.height.interpolate(&other.height, t),
+        )
+    }
+}
+
+/// Pans and zooms from a rectangle of the frames to another one, reached
+/// after `frames` frames, and resamples it to (width, height).
+///
+/// This is a `Crop` followed by a `Resize`, in a single `Resize` resampling
+/// with the rectangle as its source region: `Crop` works on whole pixels, so
+/// that a slow pan or zoom would move by one pixel every few frames and
+/// visibly jitter.
+#[derive(Debug, Clone)]
+pub struct KenBurns {
+    rect: Animated<Rect>,
+    width: u32,
+    height: u32,
+    resampler: Resampler,
+}
+
+impl KenBurns {
+    pub fn new(
+        start: Rect,
+        end: Rect,
+        easing: Easing,
+        width: u32,
+        height: u32,
+        frames: u64,
+    ) -> Self {
+        // The rectangle reaches `end` on the last frame
+        let last_frame = frames.saturating_sub(1).max(1);
+
+        Self {
+            rect: Animated::new(start).keyframe(last_frame, end, easing),
+            width,
+            height,
+            resampler: Resampler::default(),
+        }
+    }
+}
+
+impl<P: Pixel> Effect<P> for KenBurns {
+    fn apply(&mut self, frame: Frame<P>, context: &FrameContext) -> Frame<P> {
+        let dimensions = (self.width, self.height);
+        let mut output = match frame.pool() {
+            Some(pool) => pool.frame(dimensions),
+            None => Frame::from_vec(
+                ImageBuffer::<P, _>::new(self.width, self.height).into_raw(),
+                dimensions,
+            ),
+        };
+        self.resampler.resample(
+            frame.image(),
+            self.rect.value_at(context.index),
+            FilterType::Triangle,
+            output.image_mut(),
+        );
+
+        output.with_info(*frame.info())
+    }
+}
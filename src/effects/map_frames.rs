@@ -0,0 +1,36 @@
+use crate::Frame;
+use image::Pixel;
+
+/// Applies a closure to each frame, keeping the infos of the frames
+pub struct MapFrames<I, F> {
+    iter: I,
+    func: F,
+}
+
+impl<I, F> MapFrames<I, F> {
+    pub(in crate::effects) fn new(iter: I, func: F) -> Self {
+        Self { iter, func }
+    }
+}
+
+impl<I, F, P, O> Iterator for MapFrames<I, F>
+where
+    P: Pixel,
+    O: Pixel,
+    I: Iterator<Item = Frame<P>>,
+    F: FnMut(Frame<P>) -> Frame<O>,
+{
+    type Item = Frame<O>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|frame| {
+            let info = *frame.info();
+
+            (self.func)(frame).with_info(info)
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
@@ -1,50 +1,361 @@
-use crate::Frame;
+use crate::{Animated, Easing, Frame};
 use image::{Pixel, Rgb};
 
+mod effect;
+pub use self::effect::{Apply, Effect, FrameContext};
+
 mod grayscale;
-use self::grayscale::Grayscale;
+pub use self::grayscale::Grayscale;
 
 mod crop;
-use self::crop::Crop;
+pub use self::crop::Crop;
 
 mod resize;
-use self::resize::Resize;
+pub use self::resize::Resize;
+
+mod ken_burns;
+pub use self::ken_burns::{KenBurns, Rect};
 
-// TODO: only handles Rgb<u8> for now
+mod map_frames;
+use self::map_frames::MapFrames;
 
 /// Trait extension to add effects for iterators on `Frame`.
 pub trait EffectsExt: Iterator {
+    /// Applies the given effect to the frames
+    fn apply<P, O, E>(self, effect: E) -> Apply<Self, E, O>
+    where
+        Self: Sized,
+        Self: Iterator<Item = Frame<P>>,
+        P: Pixel,
+        O: Pixel,
+        E: Effect<P, O>,
+    {
+        Apply::new(self, effect)
+    }
+
     /// Applies a grayscale effect to the frame
-    fn grayscale(self) -> Grayscale<Self>
+    fn grayscale(self) -> Apply<Self, Grayscale, image::Luma<u8>>
     where
         Self: Sized,
         Self: Iterator<Item = Frame<Rgb<u8>>>,
     {
-        Grayscale::new(self)
+        self.apply(Grayscale)
     }
 
-    /// Crop the frame at (x, y) to a new (width, height)
+    /// Crop the frame at (x, y) to a new (width, height).
+    /// Parameters can be animated, for animated crops and pans.
     /// TODO: shall we handle aspect ratio instead of raw width/height?
-    fn crop<P>(self, x: u32, y: u32, width: u32, height: u32) -> Crop<Self>
+    fn crop<P>(
+        self,
+        x: impl Into<Animated<u32>>,
+        y: impl Into<Animated<u32>>,
+        width: impl Into<Animated<u32>>,
+        height: impl Into<Animated<u32>>,
+    ) -> Apply<Self, Crop, P>
     where
         Self: Sized,
         Self: Iterator<Item = Frame<P>>,
         P: Pixel,
     {
-        Crop::new(self, x, y, width, height)
+        self.apply(Crop::new(x, y, width, height))
     }
 
     /// Resize the frame.
+    /// The size can be animated, for zooms.
     /// TODO: shall we support speaking in term of aspect ratio?
-    fn resize<P>(self, width: u32, height: u32) -> Resize<Self>
+    fn resize<P>(
+        self,
+        width: impl Into<Animated<u32>>,
+        height: impl Into<Animated<u32>>,
+    ) -> Apply<Self, Resize, P>
     where
         Self: Sized,
         Self: Iterator<Item = Frame<P>>,
         P: Pixel,
     {
-        Resize::new(self, width, height)
+        self.apply(Resize::new(width, height))
+    }
+
+    /// Pans and zooms from the `start` rectangle of the frames to the `end`
+    /// one, reached on the last frame, and resamples it to (width, height).
+    /// Rectangles have subpixel coordinates, so that slow moves do not
+    /// jitter. The number of frames is taken from the iterator's size hint,
+    /// use `apply(KenBurns::new(...))` when it is unknown.
+    fn ken_burns<P>(
+        self,
+        start: Rect,
+        end: Rect,
+        easing: Easing,
+        width: u32,
+        height: u32,
+    ) -> eyre::Result<Apply<Self, KenBurns, P>>
+    where
+        Self: Sized,
+        Self: Iterator<Item = Frame<P>>,
+        P: Pixel,
+    {
+        let Some(frames) = self.size_hint().1 else {
+            eyre::bail!("unknown number of frames for the ken burns effect");
+        };
+
+        Ok(self.apply(KenBurns::new(
+            start,
+            end,
+            easing,
+            width,
+            height,
+            frames as u64,
+        )))
+    }
+
+    /// Applies the given function to each frame, for custom effects.
+    /// The function can modify the frame in place (see
+    /// `Frame::for_each_pixel_mut` and `Frame::par_rows_mut`), or return a
+    /// new one, possibly of another pixel type. The infos of the frames are
+    /// kept.
+    fn map_frames<P, O, F>(self, func: F) -> MapFrames<Self, F>
+    where
+        Self: Sized,
+        Self: Iterator<Item = Frame<P>>,
+        P: Pixel,
+        O: Pixel,
+        F: FnMut(Frame<P>) -> Frame<O>,
+    {
+        MapFrames::new(self, func)
     }
 }
 
 /// Blank implementation of trait EffectsExt for iterators on `Frame`.
 impl<I: ?Sized, P: Pixel> EffectsExt for I where I: Iterator<Item = Frame<P>> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Color, FrameSink, Generator, Pattern, Rational, Y4mWriter};
+    use image::Luma;
+
+    #[test]
+    fn chain() {
+        let rate = Rational::new(30, 1);
+        let pattern = Pattern::Solid(Color::rgb(255, 0, 0));
+        let mut out = Y4mWriter::new(Vec::new(), (108, 192), rate);
+
+        out.write_all(
+            Generator::<Rgb<u8>>::new(pattern, (320, 240), rate, 60)
+                .crop(0, 0, 90, 160)
+                .resize(108, 192)
+                .grayscale(),
+        )
+        .unwrap();
+
+        let stream = out.into_inner();
+        let header = b"YUV4MPEG2 W108 H192 F30:1 Ip A1:1 Cmono XCOLORRANGE=FULL\n";
+        assert!(stream.starts_with(header));
+        assert_eq!(stream.len(), header.len() + 60 * (6 + 108 * 192));
+        assert_eq!(stream[header.len() + 6], 76);
+    }
+
+    #[test]
+    fn frame_infos() {
+        let rate = Rational::new(30000, 1001);
+        let generator = Generator::<Rgb<u8>>::new(Pattern::ColorBars, (64, 48), rate, 10);
+        let expected = *generator.frame(7).info();
+        let frame = generator
+            .skip(7)
+            .crop(0, 0, 32, 32)
+            .resize(16, 16)
+            .ken_burns(
+                Rect::new(0.0, 0.0, 16.0, 16.0),
+                Rect::new(4.0, 4.0, 8.0, 8.0),
+                Easing::Linear,
+                8,
+                8,
+            )
+            .unwrap()
+            .grayscale()
+            .next()
+            .unwrap();
+
+        assert_eq!(frame.info(), &expected);
+        assert_eq!((expected.index, expected.pts, expected.duration), (7, 7, 1));
+        assert_eq!(expected.time_base, Rational::new(1001, 30000));
+        assert_eq!(expected.time(), rate.frame_time(7));
+        assert_eq!(expected.source, None);
+    }
+
+    #[test]
+    fn map_frames() {
+        let rate = Rational::new(25, 1);
+        let pattern = Pattern::Solid(Color::rgb(200, 100, 50));
+        let frames = Generator::<Rgb<u8>>::new(pattern, (8, 4), rate, 3)
+            .map_frames(|mut frame| {
+                // Inverts the left half, darkens the bottom row
+                frame.for_each_pixel_mut(|x, _, pixel| {
+                    if x < 4 {
+                        pixel.invert();
+                    }
+                });
+                frame.par_rows_mut(|y, row| {
+                    if y == 3 {
+                        row.fill(0);
+                    }
+                });
+                frame
+            })
+            .map_frames(|frame| frame.transform(|pixel| Luma([pixel[0]])))
+            .collect::<Vec<_>>();
+
+        assert_eq!(frames.len(), 3);
+        let image = frames[2].image();
+        assert_eq!(image.get_pixel(0, 0), &Luma([55]));
+        assert_eq!(image.get_pixel(7, 0), &Luma([200]));
+        assert_eq!(image.get_pixel(7, 3), &Luma([0]));
+        assert_eq!(frames[2].info().index, 2);
+    }
+
+    /// Delays the frames by one, repeating the first one
+    struct Delay(Option<Frame<Luma<u8>>>);
+
+    impl Effect<Luma<u8>> for Delay {
+        fn apply(&mut self, frame: Frame<Luma<u8>>, _: &FrameContext) -> Frame<Luma<u8>> {
+            let copy = Frame::from_vec(frame.as_raw().to_vec(), frame.dimensions());
+            self.0.replace(frame).unwrap_or(copy)
+        }
+
+        fn flush(&mut self, _: &FrameContext) -> Vec<Frame<Luma<u8>>> {
+            self.0.take().into_iter().collect()
+        }
+
+        fn extra_frames(&self) -> Option<usize> {
+            Some(1)
+        }
+    }
+
+    #[test]
+    fn effects() {
+        let rate = Rational::new(25, 1);
+        let frames = (0..3).map(|index| Frame::<Luma<u8>>::from_vec(vec![index; 4], (2, 2)));
+        let mut chain: Vec<Box<dyn Effect<Luma<u8>>>> = vec![Box::new(Delay(None))];
+        chain.push(Box::new(Crop::new(1, 0, 1, 2)));
+        chain.push(Box::new(Delay(None)));
+
+        let mut frames = frames.apply(chain);
+        assert_eq!(frames.size_hint(), (3, Some(5)));
+        frames.nth(2);
+        assert_eq!(frames.size_hint(), (0, Some(2)));
+        frames.next();
+        assert_eq!(frames.size_hint(), (1, Some(1)));
+
+        let frames = (0..3).map(|index| Frame::<Luma<u8>>::from_vec(vec![index; 4], (2, 2)));
+        let mut chain: Vec<Box<dyn Effect<Luma<u8>>>> = vec![Box::new(Delay(None))];
+        chain.push(Box::new(Crop::new(1, 0, 1, 2)));
+        chain.push(Box::new(Delay(None)));
+        let output = frames
+            .apply(chain)
+            .map(|frame| (frame.dimensions(), frame.as_raw()[0]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            output,
+            [
+                ((1, 2), 0),
+                ((1, 2), 0),
+                ((1, 2), 0),
+                ((1, 2), 1),
+                ((1, 2), 2)
+            ]
+        );
+
+        // Built-in effects are effects too
+        let frames = Generator::<Rgb<u8>>::new(Pattern::Solid(Color::WHITE), (4, 4), rate, 2)
+            .apply(Grayscale)
+            .apply(Delay(None))
+            .collect::<Vec<_>>();
+        assert_eq!(frames.len(), 3);
+        assert!(frames.iter().all(|frame| frame.as_raw() == [254; 16]));
+    }
+
+    #[test]
+    fn animated_crop() {
+        let rate = Rational::new(25, 1);
+        let pattern = Pattern::LinearGradient {
+            from: Color::BLACK,
+            to: Color::WHITE,
+            angle: 0.0,
+        };
+        let width = Animated::new(10)
+            .keyframe(4, 2, Easing::Linear)
+            .keyframe(8, 6, Easing::Hold);
+        let frames = Generator::<Rgb<u8>>::new(pattern, (256, 4), rate, 10)
+            .crop(
+                Animated::new(0).keyframe(9, 90, Easing::Linear),
+                0,
+                width,
+                4,
+            )
+            .collect::<Vec<_>>();
+
+        let widths = frames
+            .iter()
+            .map(|frame| frame.image().width())
+            .collect::<Vec<_>>();
+        assert_eq!(widths, [10, 8, 6, 4, 2, 2, 2, 2, 6, 6]);
+        // The crop moves to the right, on a brighter part of the gradient
+        assert!(frames[9].image().get_pixel(0, 0)[0] > frames[0].image().get_pixel(0, 0)[0]);
+        assert_eq!(frames[9].image().get_pixel(0, 0)[0], 90);
+    }
+
+    #[test]
+    fn ken_burns() {
+        let rate = Rational::new(25, 1);
+        // Each source pixel is 256 levels brighter than the previous one
+        let pattern = Pattern::LinearGradient {
+            from: Color::BLACK,
+            to: Color::WHITE,
+            angle: 0.0,
+        };
+        let frames = Generator::<Luma<u16>>::new(pattern.clone(), (256, 4), rate, 4)
+            .ken_burns(
+                Rect::new(0.0, 0.0, 64.0, 4.0),
+                Rect::new(1.5, 0.0, 64.0, 4.0),
+                Easing::Linear,
+                64,
+                4,
+            )
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(frames.len(), 4);
+
+        // Moves by half a pixel on each frame
+        let values = frames
+            .iter()
+            .map(|frame| frame.image().get_pixel(10, 2)[0] as i32)
+            .collect::<Vec<_>>();
+        for pair in values.windows(2) {
+            assert!((pair[1] - pair[0] - 128).abs() <= 2, "{:?}", values);
+        }
+
+        // Zoom out from a quarter of the frame to the whole frame
+        let frames = Generator::<Luma<u16>>::new(pattern, (256, 4), rate, 2)
+            .ken_burns(
+                Rect::new(96.0, 0.0, 64.0, 4.0),
+                Rect::new(0.0, 0.0, 256.0, 4.0),
+                Easing::EaseInOut,
+                32,
+                2,
+            )
+            .unwrap()
+            .collect::<Vec<_>>();
+        let first = frames[0].image();
+        let last = frames[1].image();
+        assert_eq!(last.dimensions(), (32, 2));
+        assert!(first.get_pixel(0, 0)[0] > 96 * 256);
+        assert!(last.get_pixel(0, 0)[0] < 8 * 256);
+        assert!(last.get_pixel(31, 0)[0] > 248 * 256);
+
+        // The last frame is unknown
+        let rect = Rect::new(0.0, 0.0, 1.0, 1.0);
+        assert!(std::iter::from_fn(|| None::<Frame<Luma<u16>>>)
+            .ken_burns(rect, rect, Easing::Linear, 1, 1)
+            .is_err());
+    }
+}
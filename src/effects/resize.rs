@@ -1,46 +1,286 @@
-use crate::Frame;
-use image::{imageops::FilterType, Pixel};
+use crate::{effects::FrameContext, Animated, Effect, Frame, Rect};
+use image::{imageops::FilterType, ImageBuffer, Pixel, Primitive};
+use num_traits::NumCast;
+use std::ops::Range;
 
-pub struct Resize<I> {
-    iter: I,
-    width: u32,
-    height: u32,
+/// Resizes the frames to a new (width, height)
+#[derive(Debug, Clone)]
+pub struct Resize {
+    width: Animated<u32>,
+    height: Animated<u32>,
+    resampler: Resampler,
 }
 
-impl<I> Resize<I> {
-    pub(in crate::effects) fn new(iter: I, width: u32, height: u32) -> Self {
-        Resize {
-            iter,
-            width,
-            height,
+impl Resize {
+    pub fn new(width: impl Into<Animated<u32>>, height: impl Into<Animated<u32>>) -> Self {
+        Self {
+            width: width.into(),
+            height: height.into(),
+            resampler: Resampler::default(),
         }
     }
 }
 
-/// TODO: this has horrible performance, resizing a relatively small frame to
-/// a short 9:16 format (1920x1080) using lanczos takes more than 1 second.
-impl<I, P> Iterator for Resize<I>
-where
-    P: Pixel + 'static, // static lifetime is required by `resize`
-    I: Iterator<Item = Frame<P>>,
-{
-    type Item = I::Item;
+impl<P: Pixel> Effect<P> for Resize {
+    fn apply(&mut self, frame: Frame<P>, context: &FrameContext) -> Frame<P> {
+        let dimensions = (
+            self.width.value_at(context.index),
+            self.height.value_at(context.index),
+        );
+        let (width, height) = frame.dimensions();
+        let region = Rect::new(0.0, 0.0, width as f32, height as f32);
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.iter.next().map(|frame| {
-            let new_image = image::imageops::resize(
-                frame.image(),
-                self.width,
-                self.height,
-                FilterType::Lanczos3,
-            );
-            let dimensions = new_image.dimensions();
+        // Resized frames reuse the buffers of the previous ones
+        let mut resized = match frame.pool() {
+            Some(pool) => pool.frame(dimensions),
+            None => Frame::from_vec(
+                ImageBuffer::<P, _>::new(dimensions.0, dimensions.1).into_raw(),
+                dimensions,
+            ),
+        };
+        self.resampler.resample(
+            frame.image(),
+            region,
+            FilterType::Lanczos3,
+            resized.image_mut(),
+        );
 
-            Frame::from_vec(new_image.into_raw(), dimensions)
-        })
+        resized.with_info(*frame.info())
     }
+}
+
+/// Resamples a region of an image into another one, with a separable filter.
+/// The region has subpixel coordinates, so that a region moving slowly from
+/// frame to frame does not jitter. The filter is widened when downscaling,
+/// to average all the source pixels instead of aliasing.
+/// Buffers are kept between frames.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Resampler {
+    columns: Taps,
+    rows: Taps,
+    horizontal: Vec<f32>, // rows of the source resampled horizontally
+}
+
+impl Resampler {
+    /// Resamples the `region` of `source` to the dimensions of `output`
+    pub(crate) fn resample<P: Pixel>(
+        &mut self,
+        source: &ImageBuffer<P, Vec<P::Subpixel>>,
+        region: Rect,
+        filter: FilterType,
+        output: &mut ImageBuffer<P, Vec<P::Subpixel>>,
+    ) {
+        let (source_width, source_height) = source.dimensions();
+        let (width, height) = output.dimensions();
+        if source_width == 0 || source_height == 0 {
+            output.fill(P::Subpixel::DEFAULT_MIN_VALUE);
+            return;
+        }
+
+        let columns = &mut self.columns;
+        let rows = &mut self.rows;
+        columns.compute(filter, region.x, region.width, source_width, width);
+        rows.compute(filter, region.y, region.height, source_height, height);
+
+        // Only the rows read by the vertical pass are resampled horizontally
+        let first_row = rows.spans.iter().map(|span| span.0).min().unwrap_or(0);
+        let end_row = rows
+            .spans
+            .iter()
+            .map(|span| span.0 + span.1.len())
+            .max()
+            .unwrap_or(0);
+
+        let channels = P::CHANNEL_COUNT as usize;
+        let (stride, row) = (source_width as usize * channels, width as usize * channels);
+        let source = source.as_raw();
+        self.horizontal.clear();
+        for line in first_row..end_row {
+            let line = &source[line * stride..(line + 1) * stride];
+            for (first, weights) in columns.iter() {
+                for channel in 0..channels {
+                    let value = weights
+                        .iter()
+                        .enumerate()
+                        .map(|(tap, weight)| {
+                            weight * to_f32(line[(first + tap) * channels + channel])
+                        })
+                        .sum::<f32>();
+                    self.horizontal.push(value);
+                }
+            }
+        }
+
+        let (min, max) = (
+            to_f32(P::Subpixel::DEFAULT_MIN_VALUE),
+            to_f32(P::Subpixel::DEFAULT_MAX_VALUE),
+        );
+        // Floating point subpixels go from 0 to 1, the others are rounded
+        let round = max > 1.0;
+        let horizontal = &self.horizontal;
+        for ((first, weights), line) in rows.iter().zip(output.chunks_exact_mut(row)) {
+            for (index, subpixel) in line.iter_mut().enumerate() {
+                let value = weights
+                    .iter()
+                    .enumerate()
+                    .map(|(tap, weight)| {
+                        weight * horizontal[(first + tap - first_row) * row + index]
+                    })
+                    .sum::<f32>();
+                let value = value.clamp(min, max);
+                let value = if round { value.round() } else { value };
+                *subpixel = NumCast::from(value).unwrap_or(P::Subpixel::DEFAULT_MIN_VALUE);
+            }
+        }
+    }
+}
+
+/// Source pixels and weights of each output pixel, along one axis
+#[derive(Debug, Clone, Default)]
+struct Taps {
+    spans: Vec<(usize, Range<usize>)>, // first source pixel, and its weights
+    weights: Vec<f32>,
+}
+
+impl Taps {
+    /// Maps `length` source pixels from `start` to `output` pixels
+    fn compute(&mut self, filter: FilterType, start: f32, length: f32, source: u32, output: u32) {
+        self.spans.clear();
+        self.weights.clear();
+
+        let scale = length / output as f32;
+        let filter_scale = scale.max(1.0);
+        let support = filter_support(filter) * filter_scale;
+        for index in 0..output {
+            // Center of the output pixel, in the source pixels coordinates
+            let center = start + (index as f32 + 0.5) * scale;
+            let first = (center - support).floor().max(0.0) as usize;
+            let end = ((center + support).ceil().max(0.0) as usize).min(source as usize);
+
+            let offset = self.weights.len();
+            for pixel in first..end {
+                let distance = (pixel as f32 + 0.5 - center) / filter_scale;
+                self.weights.push(filter_kernel(filter, distance));
+            }
+            let sum = self.weights[offset..].iter().sum::<f32>();
+            if sum.abs() > f32::EPSILON {
+                self.weights[offset..]
+                    .iter_mut()
+                    .for_each(|weight| *weight /= sum);
+                self.spans.push((first, offset..self.weights.len()));
+            } else {
+                // Out of the source: the nearest pixel is repeated
+                self.weights.truncate(offset);
+                self.weights.push(1.0);
+                let nearest = (center.floor().max(0.0) as usize).min(source as usize - 1);
+                self.spans.push((nearest, offset..offset + 1));
+            }
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (usize, &[f32])> + '_ {
+        self.spans
+            .iter()
+            .map(|(first, weights)| (*first, &self.weights[weights.clone()]))
+    }
+}
+
+/// Distance from the center beyond which the filter is 0, in pixels
+fn filter_support(filter: FilterType) -> f32 {
+    match filter {
+        FilterType::Nearest => 0.5,
+        FilterType::Triangle => 1.0,
+        FilterType::CatmullRom => 2.0,
+        FilterType::Gaussian => 3.0,
+        FilterType::Lanczos3 => 3.0,
+    }
+}
+
+/// Weight of a pixel at `x` pixels from the center, the same kernels as
+/// `imageops::resize`
+fn filter_kernel(filter: FilterType, x: f32) -> f32 {
+    let sinc = |x: f32| {
+        if x == 0.0 {
+            1.0
+        } else {
+            let x = x * std::f32::consts::PI;
+            x.sin() / x
+        }
+    };
+    let x = x.abs();
+
+    match filter {
+        FilterType::Nearest if x < 0.5 => 1.0,
+        FilterType::Triangle if x < 1.0 => 1.0 - x,
+        // Cubic with B = 0 and C = 0.5
+        FilterType::CatmullRom if x < 1.0 => 1.5 * x * x * x - 2.5 * x * x + 1.0,
+        FilterType::CatmullRom if x < 2.0 => -0.5 * x * x * x + 2.5 * x * x - 4.0 * x + 2.0,
+        // Sigma of 0.5, the normalization of the weights makes the factor useless
+        FilterType::Gaussian if x < 3.0 => (-2.0 * x * x).exp(),
+        FilterType::Lanczos3 if x < 3.0 => sinc(x) * sinc(x / 3.0),
+        _ => 0.0,
+    }
+}
+
+fn to_f32<S: Primitive>(value: S) -> f32 {
+    value.to_f32().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FramePool;
+    use image::Luma;
+
+    #[test]
+    fn pooled() {
+        let pool = FramePool::new(4);
+        let mut resize = Resize::new(4, 2);
+        let context = FrameContext {
+            index: 0,
+            nb_frames: None,
+        };
+
+        // The buffer of a frame resized before, kept by the pool
+        let source = pool.frame::<Luma<u8>>((8, 4));
+        let recycled = pool.frame::<Luma<u8>>((4, 2)).as_raw().as_ptr();
+
+        let resized = resize.apply(source, &context);
+        assert_eq!(resized.as_raw().as_ptr(), recycled);
+        drop(resized);
+        assert_eq!(pool.len(), 2);
+
+        let resized = resize.apply(pool.frame::<Luma<u8>>((8, 4)), &context);
+        assert_eq!(resized.as_raw().as_ptr(), recycled);
+        assert_eq!(pool.len(), 1);
+    }
+
+    #[test]
+    fn resample() {
+        let source = ImageBuffer::from_fn(8, 2, |x, _| Luma([x as u16 * 1000]));
+        let mut resampler = Resampler::default();
+
+        // Identity
+        let mut output = ImageBuffer::<Luma<u16>, _>::new(8, 2);
+        let region = Rect::new(0.0, 0.0, 8.0, 2.0);
+        resampler.resample(&source, region, FilterType::Lanczos3, &mut output);
+        assert_eq!(output, source);
+
+        // Half a pixel to the right
+        let region = Rect::new(0.5, 0.0, 4.0, 2.0);
+        let mut output = ImageBuffer::<Luma<u16>, _>::new(4, 2);
+        resampler.resample(&source, region, FilterType::Triangle, &mut output);
+        assert_eq!(output.get_pixel(1, 1)[0], 1500);
+        assert_eq!(output.get_pixel(3, 0)[0], 3500);
+
+        // Downscaling averages the pixels
+        let mut output = ImageBuffer::<Luma<u16>, _>::new(1, 1);
+        resampler.resample(&source, region, FilterType::Nearest, &mut output);
+        assert_eq!(output.get_pixel(0, 0)[0], 2000);
 
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        self.iter.size_hint()
+        // Out of the source
+        let region = Rect::new(20.0, 0.0, 1.0, 1.0);
+        resampler.resample(&source, region, FilterType::Lanczos3, &mut output);
+        assert_eq!(output.get_pixel(0, 0)[0], 7000);
     }
 }
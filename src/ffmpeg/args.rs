@@ -0,0 +1,43 @@
+use std::{ffi::OsString, path::Path};
+
+/// Returns a path as an FFMpeg input or output argument.
+/// FFMpeg takes arguments starting with `-` as options, and `name:...` as an
+/// URL of the `name` protocol: such paths are given with the `file:`
+/// protocol. Paths are passed as is otherwise, whatever their encoding.
+pub(crate) fn path_arg(path: &Path) -> OsString {
+    let bytes = path.as_os_str().as_encoded_bytes();
+    if !bytes.starts_with(b"-") && !bytes.contains(&b':') {
+        return path.as_os_str().to_owned();
+    }
+
+    let mut arg = OsString::from("file:");
+    arg.push(path.as_os_str());
+    arg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape() {
+        assert_eq!(path_arg(Path::new("/videos/in.mp4")), "/videos/in.mp4");
+        assert_eq!(path_arg(Path::new("-in.mp4")), "file:-in.mp4");
+        assert_eq!(path_arg(Path::new("http:in.mp4")), "file:http:in.mp4");
+        assert_eq!(
+            path_arg(Path::new("/videos/12:30.mp4")),
+            "file:/videos/12:30.mp4"
+        );
+        assert_eq!(path_arg(Path::new("dir/-in.mp4")), "dir/-in.mp4");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let name = std::ffi::OsStr::from_bytes(b"-caf\xe9.mp4");
+        let arg = path_arg(Path::new(name));
+        assert_eq!(arg.as_bytes(), b"file:-caf\xe9.mp4");
+    }
+}
@@ -1,119 +1,215 @@
+use crate::{
+    metadata::{AudioStreamInfo, ClipMetadata, FormatInfo, VideoStreamInfo},
+    PixelFormat, Rational, StreamSelector, Timecode,
+};
 use std::collections::HashMap;
+use std::io::Read;
 use std::path::PathBuf;
 use std::process::{Command, Stdio};
+use std::time::Duration;
 
 #[derive(serde::Deserialize, Debug, Clone)]
 #[serde(tag = "codec_type")]
-#[allow(dead_code)]
+#[allow(dead_code, clippy::large_enum_variant)]
 pub(crate) enum FFMpegStream {
     #[serde(rename = "video")]
     Video {
-        index: u8,
+        index: u32,
+        #[serde(default)]
         codec_name: String,
+        #[serde(default)]
         codec_long_name: String,
-        profile: String,
-        codec_tag_string: String,
-        codec_tag: String,
+        profile: Option<String>,
+        codec_tag_string: Option<String>,
+        codec_tag: Option<String>,
         width: u32,
         height: u32,
-        coded_width: u16,
-        coded_height: u16,
-        closed_captions: u16,
-        film_grain: u32,
-        has_b_frames: u32,
-        sample_aspect_ratio: Option<String>, // make some kind of aspect ratio struct?
+        coded_width: Option<u32>,
+        coded_height: Option<u32>,
+        closed_captions: Option<u16>,
+        film_grain: Option<u32>,
+        has_b_frames: Option<u32>,
+        sample_aspect_ratio: Option<String>,
         display_aspect_ratio: Option<String>,
+        #[serde(default)]
         pix_fmt: String,
-        level: u32,
+        level: Option<i32>,
         color_range: Option<String>,
         color_space: Option<String>,
-        chroma_location: String,
-        field_order: String,
-        refs: u32,
-        #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_bool_from_anything")]
-        is_avc: bool,
-        #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
-        nal_length_size: u32,
-        id: String,
+        color_primaries: Option<String>,
+        color_transfer: Option<String>,
+        chroma_location: Option<String>,
+        field_order: Option<String>,
+        refs: Option<u32>,
+        is_avc: Option<String>,
+        #[serde(default, deserialize_with = "optional_number")]
+        nal_length_size: Option<u32>,
+        id: Option<String>,
+        #[serde(default)]
         r_frame_rate: String,
+        #[serde(default)]
         avg_frame_rate: String,
+        #[serde(default)]
         time_base: String,
-        start_pts: u32,
-        #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
-        start_time: f32,
-        duration_ts: u32,
-        #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
-        duration: f32,
-        #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
-        bit_rate: u32,
-        #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
-        bits_per_raw_sample: u16,
-        #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
-        nb_frames: u32,
-        extradata_size: u32,
+        start_pts: Option<i64>,
+        #[serde(default, deserialize_with = "optional_number")]
+        start_time: Option<f64>,
+        duration_ts: Option<u64>,
+        #[serde(default, deserialize_with = "optional_number")]
+        duration: Option<f64>,
+        #[serde(default, deserialize_with = "optional_number")]
+        bit_rate: Option<u64>,
+        #[serde(default, deserialize_with = "optional_number")]
+        bits_per_raw_sample: Option<u16>,
+        #[serde(default, deserialize_with = "optional_number")]
+        nb_frames: Option<u64>,
+        extradata_size: Option<u32>,
+        #[serde(default)]
         disposition: HashMap<String, u32>,
+        #[serde(default)]
         tags: HashMap<String, String>,
+        #[serde(default)]
+        side_data_list: Vec<FFMpegSideData>,
     },
     #[serde(rename = "audio")]
     Audio {
         index: u32,
+        #[serde(default)]
         codec_name: String,
+        #[serde(default)]
         codec_long_name: String,
-        profile: String,
-        codec_tag_string: String,
-        codec_tag: String,
+        profile: Option<String>,
+        codec_tag_string: Option<String>,
+        codec_tag: Option<String>,
+        #[serde(default)]
         sample_fmt: String,
-        #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
-        sample_rate: u32,
+        #[serde(default, deserialize_with = "optional_number")]
+        sample_rate: Option<u32>,
+        #[serde(default)]
         channels: u16,
-        channel_layout: String, // stereo/mono I guess ?
-        bits_per_sample: u32,
-        initial_padding: u32,
-        id: String,
+        channel_layout: Option<String>,
+        bits_per_sample: Option<u32>,
+        initial_padding: Option<u32>,
+        id: Option<String>,
+        #[serde(default)]
         r_frame_rate: String,
+        #[serde(default)]
         avg_frame_rate: String,
+        #[serde(default)]
         time_base: String,
-        start_pts: u32,
-        #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
-        start_time: f32,
-        duration_ts: u32,
-        #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
-        duration: f32,
-        #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
-        bit_rate: u32,
-        #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
-        nb_frames: u32,
-        extradata_size: u32,
+        start_pts: Option<i64>,
+        #[serde(default, deserialize_with = "optional_number")]
+        start_time: Option<f64>,
+        duration_ts: Option<u64>,
+        #[serde(default, deserialize_with = "optional_number")]
+        duration: Option<f64>,
+        #[serde(default, deserialize_with = "optional_number")]
+        bit_rate: Option<u64>,
+        #[serde(default, deserialize_with = "optional_number")]
+        nb_frames: Option<u64>,
+        extradata_size: Option<u32>,
+        #[serde(default)]
+        disposition: HashMap<String, u32>,
+        #[serde(default)]
+        tags: HashMap<String, String>,
+    },
+    #[serde(rename = "subtitle")]
+    Subtitle {
+        index: u32,
+        #[serde(default)]
+        codec_name: String,
+        #[serde(default)]
         disposition: HashMap<String, u32>,
+        #[serde(default)]
         tags: HashMap<String, String>,
     },
+    // ie timecode tracks in QuickTime files
+    #[serde(rename = "data")]
+    Data {
+        index: u32,
+        #[serde(default)]
+        codec_tag_string: String,
+        #[serde(default)]
+        tags: HashMap<String, String>,
+    },
+    // ie fonts embedded in Matroska files
+    #[serde(rename = "attachment")]
+    Attachment {
+        index: u32,
+        #[serde(default)]
+        codec_name: String,
+        #[serde(default)]
+        tags: HashMap<String, String>,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub(crate) struct FFMpegSideData {
+    // Only set for the display matrix, counter-clockwise
+    #[serde(default)]
+    rotation: Option<i32>,
 }
 
 #[derive(serde::Deserialize, Debug, Clone)]
 #[allow(dead_code)]
 pub(crate) struct FFMpegFormat {
+    #[serde(default)]
     filename: String,
-    nb_streams: u16,
-    nb_programs: u16,
+    #[serde(default)]
+    nb_streams: u32,
+    #[serde(default)]
+    nb_programs: u32,
+    #[serde(default)]
     format_name: String,
+    #[serde(default)]
     format_long_name: String,
-    #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
-    start_time: f32,
-    #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
-    duration: f32,
-    #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
-    size: u64,
-    #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
-    bit_rate: u32,
-    probe_score: u32,
+    #[serde(default, deserialize_with = "optional_number")]
+    start_time: Option<f64>,
+    #[serde(default, deserialize_with = "optional_number")]
+    duration: Option<f64>,
+    #[serde(default, deserialize_with = "optional_number")]
+    size: Option<u64>,
+    #[serde(default, deserialize_with = "optional_number")]
+    bit_rate: Option<u64>,
+    probe_score: Option<u32>,
+    #[serde(default)]
     tags: HashMap<String, String>,
 }
 
 #[derive(serde::Deserialize, Debug, Clone)]
 #[allow(dead_code)]
 pub(crate) struct FFMpegInfos {
+    #[serde(default)]
     streams: Vec<FFMpegStream>,
     format: FFMpegFormat,
+    // Index of the video stream the getters report about
+    #[serde(skip)]
+    video_index: Option<u32>,
+}
+
+/// ffprobe reports numbers either as JSON numbers or as strings. Missing, empty
+/// or unparsable values (`N/A`) are all mapped to None.
+fn optional_number<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: std::str::FromStr,
+{
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Number(serde_json::Number),
+        String(String),
+    }
+
+    let raw: Option<Raw> = serde::Deserialize::deserialize(deserializer)?;
+
+    Ok(match raw {
+        Some(Raw::Number(number)) => number.to_string().parse().ok(),
+        Some(Raw::String(string)) => string.trim().parse().ok(),
+        None => None,
+    })
 }
 
 impl FFMpegInfos {
@@ -131,9 +227,8 @@ impl FFMpegInfos {
                 "json",
                 "-show_format",
                 "-show_streams",
-                path.to_str()
-                    .ok_or(eyre::eyre!("path is not utf8 string"))?,
             ])
+            .arg(super::path_arg(path))
             .stdin(Stdio::null())
             .stdout(Stdio::piped())
             .stderr(Stdio::null())
@@ -144,58 +239,644 @@ impl FFMpegInfos {
             eyre::bail!("Call to ffmpeg failed: {:?}", output.status);
         }
 
-        let out = String::from_utf8_lossy(&output.stdout);
+        Self::from_json(String::from_utf8_lossy(&output.stdout).as_ref())
+    }
 
-        serde_json::from_str(out.as_ref())
-            .map_err(|err| eyre::eyre!("unable to parse JSON: {:?}", err))
+    /// Runs ffprobe on the content of a file, written to its input
+    pub(crate) fn from_bytes(data: &[u8]) -> eyre::Result<Self> {
+        Self::from_reader(data)
+    }
+
+    /// Runs ffprobe on what is read from `reader`, copied to its input
+    pub(crate) fn from_reader(mut reader: impl Read + Send) -> eyre::Result<Self> {
+        let mut child = Command::new("ffprobe")
+            .args([
+                "-v",
+                "quiet",
+                "-print_format",
+                "json",
+                "-show_format",
+                "-show_streams",
+                "pipe:0",
+            ])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|err| eyre::eyre!("unable to get output: {:?}", err))?;
+
+        let mut stdin = child.stdin.take().expect("cannot get stdin");
+        let output = std::thread::scope(|scope| {
+            // ffprobe may stop reading before the end, the write then fails
+            scope.spawn(move || {
+                let _ = std::io::copy(&mut reader, &mut stdin);
+            });
+            child.wait_with_output()
+        })
+        .map_err(|err| eyre::eyre!("unable to get output: {:?}", err))?;
+
+        if !output.status.success() {
+            eyre::bail!("Call to ffprobe failed: {:?}", output.status);
+        }
+
+        Self::from_json(String::from_utf8_lossy(&output.stdout).as_ref())
+    }
+
+    /// Parses the JSON output of ffprobe
+    pub(crate) fn from_json(json: &str) -> eyre::Result<Self> {
+        let mut infos: Self = serde_json::from_str(json)
+            .map_err(|err| eyre::eyre!("unable to parse JSON: {:?}", err))?;
+        infos.video_index = StreamSelector::Default.find(&infos.video_streams()).ok();
+
+        Ok(infos)
+    }
+
+    /// Selects the video stream the other methods report about, the first
+    /// one that is not a cover art by default
+    pub(crate) fn select_video_stream(&mut self, selector: StreamSelector) -> eyre::Result<()> {
+        let index = selector
+            .find(&self.video_streams())
+            .map_err(|_| eyre::eyre!("no video stream matches {:?}", selector))?;
+        self.video_index = Some(index);
+
+        Ok(())
+    }
+
+    /// Returns the index in the container of the selected video stream
+    pub(crate) fn video_index(&self) -> Option<u32> {
+        self.video_index
+    }
+
+    /// Returns the (index, is a cover art) of the video streams
+    fn video_streams(&self) -> Vec<(u32, bool)> {
+        self.streams
+            .iter()
+            .filter_map(|stream| match stream {
+                FFMpegStream::Video {
+                    index, disposition, ..
+                } => Some((*index, is_attached_picture(disposition))),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the streams, without the video streams that are not selected
+    fn selected_streams(&self) -> impl Iterator<Item = &FFMpegStream> {
+        self.streams.iter().filter(|stream| match stream {
+            FFMpegStream::Video { index, .. } => Some(*index) == self.video_index,
+            _ => true,
+        })
     }
 
     /// Returns the dimensions of the video, None if there is no video stream
     pub(crate) fn dimensions(&self) -> Option<(u32, u32)> {
-        self.streams.iter().find_map(|stream| match stream {
+        self.selected_streams().find_map(|stream| match stream {
             FFMpegStream::Video { width, height, .. } => Some((*width, *height)),
-            FFMpegStream::Audio { .. } => None,
+            _ => None,
         })
     }
 
-    /// Returns the right Pixel tuple of name and pixel depth
-    // TODO: the depth should be strong typed
-    pub(crate) fn pixel(&self) -> Option<(&'static str, u8)> {
-        self.streams.iter().find_map(|stream| match stream {
-            FFMpegStream::Video { pix_fmt, .. } => match pix_fmt.as_str() {
-                "rgb24" => Some(("rgb24", 3)),
-                "yuv420p" => Some(("yuv420p", 3)),
+    /// Returns the pixel format of the video stream. None if there is no video stream.
+    pub(crate) fn pixel_format(&self) -> Option<PixelFormat> {
+        self.selected_streams().find_map(|stream| match stream {
+            FFMpegStream::Video { pix_fmt, .. } => Some(PixelFormat::from_name(pix_fmt)),
+            _ => None,
+        })
+    }
+
+    /// Returns the decoder to use to keep the alpha channel of the video stream,
+    /// if the default one drops it.
+    /// VP8/VP9 store the alpha channel apart, only libvpx decodes it.
+    pub(crate) fn alpha_decoder(&self) -> Option<&'static str> {
+        self.selected_streams().find_map(|stream| match stream {
+            FFMpegStream::Video {
+                codec_name, tags, ..
+            } if has_alpha_mode(tags) => match codec_name.as_str() {
+                "vp8" => Some("libvpx"),
+                "vp9" => Some("libvpx-vp9"),
                 _ => None,
             },
-            FFMpegStream::Audio { .. } => None,
+            _ => None,
         })
     }
 
-    /// Returns the number of frames per second. None if there is no video stream.
-    pub(crate) fn fps(&self) -> Option<f32> {
-        self.streams.iter().find_map(|stream| match stream {
+    /// Returns the frame rate of the video stream. None if there is no video stream.
+    /// The average frame rate is preferred, as `r_frame_rate` is only the
+    /// lowest rate at which all the timestamps can be represented, which for
+    /// variable frame rate videos can be much higher than the real one.
+    /// For constant frame rate videos, the exact base rate is kept instead of
+    /// a rounded average.
+    pub(crate) fn frame_rate(&self) -> Option<Rational> {
+        self.selected_streams().find_map(|stream| match stream {
             FFMpegStream::Video {
-                duration,
-                nb_frames,
+                r_frame_rate,
+                avg_frame_rate,
                 ..
-            } => Some(*nb_frames as f32 / *duration),
-            FFMpegStream::Audio { .. } => None,
+            } => stream_frame_rate(r_frame_rate, avg_frame_rate),
+            _ => None,
         })
     }
 
-    /// Returns the number of frames in the video strea. None if there is no video stream.
+    /// Returns the time base of the video stream timestamps
+    pub(crate) fn time_base(&self) -> Option<Rational> {
+        self.selected_streams().find_map(|stream| match stream {
+            FFMpegStream::Video { time_base, .. } => time_base.parse().ok(),
+            _ => None,
+        })
+    }
+
+    /// Returns the timestamp of the first frame of the video stream, in
+    /// `time_base` units
+    pub(crate) fn start_pts(&self) -> Option<i64> {
+        self.selected_streams().find_map(|stream| match stream {
+            FFMpegStream::Video { start_pts, .. } => *start_pts,
+            _ => None,
+        })
+    }
+
+    /// Returns true if the video stream has a variable frame rate, ie the
+    /// base frame rate differs from the average one.
+    pub(crate) fn is_variable_frame_rate(&self) -> bool {
+        self.selected_streams()
+            .find_map(|stream| match stream {
+                FFMpegStream::Video {
+                    r_frame_rate,
+                    avg_frame_rate,
+                    ..
+                } => Some(is_variable_frame_rate(r_frame_rate, avg_frame_rate)),
+                _ => None,
+            })
+            .unwrap_or(false)
+    }
+
+    /// Returns the timecode of the first frame, as stored in the `timecode`
+    /// tag of the video stream, of a data stream (QuickTime `tmcd` track), or
+    /// of the container.
+    pub(crate) fn timecode(&self) -> Option<Timecode> {
+        let frame_rate = self.frame_rate()?;
+        self.selected_streams()
+            .find_map(|stream| match stream {
+                FFMpegStream::Video { tags, .. } | FFMpegStream::Data { tags, .. } => {
+                    tags.get("timecode")
+                }
+                _ => None,
+            })
+            .or_else(|| self.format.tags.get("timecode"))
+            .and_then(|timecode| Timecode::parse(timecode, frame_rate).ok())
+    }
+
+    /// Returns the number of frames in the video stream. None if there is no video stream.
+    /// Some containers (WebM, Matroska, image sequences) do not store it, in this
+    /// case it is estimated from the duration and the frame rate.
     pub(crate) fn nb_frames(&self) -> Option<usize> {
-        self.streams.iter().find_map(|stream| match stream {
-            FFMpegStream::Video { nb_frames, .. } => Some(*nb_frames as usize),
-            FFMpegStream::Audio { .. } => None,
+        self.selected_streams().find_map(|stream| match stream {
+            FFMpegStream::Video {
+                nb_frames: Some(nb_frames),
+                ..
+            } => Some(*nb_frames as usize),
+            FFMpegStream::Video { .. } => {
+                let duration = to_duration(self.duration()?)?;
+                Some(self.frame_rate()?.frames_in(duration) as usize)
+            }
+            _ => None,
         })
     }
 
     /// Returns the duration in seconds. None if there is no video stream.
-    pub(crate) fn duration(&self) -> Option<f32> {
-        self.streams.iter().find_map(|stream| match stream {
-            FFMpegStream::Video { duration, .. } => Some(*duration),
-            FFMpegStream::Audio { .. } => None,
+    pub(crate) fn duration(&self) -> Option<f64> {
+        self.selected_streams().find_map(|stream| match stream {
+            FFMpegStream::Video { duration, tags, .. } => {
+                stream_duration(*duration, tags).or(self.format.duration)
+            }
+            _ => None,
         })
     }
+
+    /// Builds the public metadata out of what ffprobe reported
+    pub(crate) fn metadata(&self) -> ClipMetadata {
+        let format = FormatInfo {
+            format_name: self.format.format_name.clone(),
+            format_long_name: self.format.format_long_name.clone(),
+            nb_streams: self.format.nb_streams,
+            duration: self.format.duration.and_then(to_duration),
+            size: self.format.size,
+            bit_rate: self.format.bit_rate,
+            creation_time: self.format.tags.get("creation_time").cloned(),
+            tags: self.format.tags.clone(),
+        };
+
+        let mut video_streams = Vec::new();
+        let mut audio_streams = Vec::new();
+        for stream in &self.streams {
+            match stream {
+                FFMpegStream::Video {
+                    index,
+                    codec_name,
+                    codec_long_name,
+                    profile,
+                    width,
+                    height,
+                    sample_aspect_ratio,
+                    display_aspect_ratio,
+                    pix_fmt,
+                    color_range,
+                    color_space,
+                    color_primaries,
+                    color_transfer,
+                    r_frame_rate,
+                    avg_frame_rate,
+                    time_base,
+                    duration,
+                    bit_rate,
+                    nb_frames,
+                    disposition,
+                    tags,
+                    side_data_list,
+                    ..
+                } => video_streams.push(VideoStreamInfo {
+                    index: *index,
+                    codec_name: codec_name.clone(),
+                    codec_long_name: codec_long_name.clone(),
+                    profile: profile.clone(),
+                    width: *width,
+                    height: *height,
+                    pixel_format: PixelFormat::from_name(pix_fmt),
+                    has_alpha: has_alpha_mode(tags) || PixelFormat::from_name(pix_fmt).has_alpha(),
+                    frame_rate: frame_rate(r_frame_rate),
+                    avg_frame_rate: frame_rate(avg_frame_rate),
+                    variable_frame_rate: is_variable_frame_rate(r_frame_rate, avg_frame_rate),
+                    time_base: time_base.parse().ok(),
+                    duration: stream_duration(*duration, tags).and_then(to_duration),
+                    nb_frames: *nb_frames,
+                    bit_rate: *bit_rate,
+                    sample_aspect_ratio: sample_aspect_ratio
+                        .as_deref()
+                        .and_then(|ratio| ratio.parse().ok()),
+                    display_aspect_ratio: display_aspect_ratio
+                        .as_deref()
+                        .and_then(|ratio| ratio.parse().ok()),
+                    rotation: rotation(tags, side_data_list),
+                    attached_picture: is_attached_picture(disposition),
+                    timecode: stream_frame_rate(r_frame_rate, avg_frame_rate)
+                        .zip(tags.get("timecode"))
+                        .and_then(|(rate, timecode)| Timecode::parse(timecode, rate).ok()),
+                    color_space: color_space.clone(),
+                    color_range: color_range.clone(),
+                    color_primaries: color_primaries.clone(),
+                    color_transfer: color_transfer.clone(),
+                    tags: tags.clone(),
+                }),
+                FFMpegStream::Audio {
+                    index,
+                    codec_name,
+                    codec_long_name,
+                    profile,
+                    sample_fmt,
+                    sample_rate,
+                    channels,
+                    channel_layout,
+                    time_base,
+                    duration,
+                    bit_rate,
+                    nb_frames,
+                    tags,
+                    ..
+                } => audio_streams.push(AudioStreamInfo {
+                    index: *index,
+                    codec_name: codec_name.clone(),
+                    codec_long_name: codec_long_name.clone(),
+                    profile: profile.clone(),
+                    sample_fmt: sample_fmt.clone(),
+                    sample_rate: sample_rate.unwrap_or_default(),
+                    channels: *channels,
+                    channel_layout: channel_layout.clone(),
+                    time_base: time_base.parse().ok(),
+                    duration: stream_duration(*duration, tags).and_then(to_duration),
+                    nb_frames: *nb_frames,
+                    bit_rate: *bit_rate,
+                    tags: tags.clone(),
+                }),
+                _ => {}
+            }
+        }
+
+        ClipMetadata {
+            format,
+            video_streams,
+            audio_streams,
+        }
+    }
+}
+
+/// WebM stores the alpha channel of VP8/VP9 streams as side data, flagged
+/// with an `alpha_mode` tag
+fn has_alpha_mode(tags: &HashMap<String, String>) -> bool {
+    tags.iter()
+        .any(|(key, value)| key.eq_ignore_ascii_case("alpha_mode") && value == "1")
+}
+
+/// Cover art is stored as a video stream with a single picture
+fn is_attached_picture(disposition: &HashMap<String, u32>) -> bool {
+    disposition.get("attached_pic") == Some(&1)
+}
+
+/// Parses a frame rate, FFMpeg uses `0/0` when it is unknown
+fn frame_rate(rate: &str) -> Option<Rational> {
+    rate.parse::<Rational>().ok().filter(|rate| rate.num() > 0)
+}
+
+/// Returns the frame rate of a video stream: the average one for variable
+/// frame rate streams, the exact base one otherwise
+fn stream_frame_rate(r_frame_rate: &str, avg_frame_rate: &str) -> Option<Rational> {
+    if is_variable_frame_rate(r_frame_rate, avg_frame_rate) {
+        frame_rate(avg_frame_rate)
+    } else {
+        frame_rate(r_frame_rate).or_else(|| frame_rate(avg_frame_rate))
+    }
+}
+
+/// Relative difference between the base and the average frame rates above
+/// which a stream is variable frame rate. The average is computed from the
+/// frame count and duration, which are rounded, so that it is rarely exactly
+/// the base rate even for a constant frame rate.
+const VARIABLE_FRAME_RATE_TOLERANCE: f64 = 1e-3;
+
+fn is_variable_frame_rate(r_frame_rate: &str, avg_frame_rate: &str) -> bool {
+    match (frame_rate(r_frame_rate), frame_rate(avg_frame_rate)) {
+        (Some(r_frame_rate), Some(avg_frame_rate)) => {
+            let (r_frame_rate, avg_frame_rate) = (r_frame_rate.as_f64(), avg_frame_rate.as_f64());
+            (r_frame_rate - avg_frame_rate).abs() > VARIABLE_FRAME_RATE_TOLERANCE * r_frame_rate
+        }
+        _ => false,
+    }
+}
+
+/// Returns the duration of a stream in seconds.
+/// Matroska does not store it in the stream itself but in a `DURATION` tag
+/// formatted as `HH:MM:SS.nnnnnnnnn`.
+fn stream_duration(duration: Option<f64>, tags: &HashMap<String, String>) -> Option<f64> {
+    duration.or_else(|| {
+        let tag = tags.get("DURATION")?;
+        let mut parts = tag.splitn(3, ':');
+        let hours = parts.next()?.parse::<f64>().ok()?;
+        let minutes = parts.next()?.parse::<f64>().ok()?;
+        let seconds = parts.next()?.parse::<f64>().ok()?;
+        Some(hours * 3600. + minutes * 60. + seconds)
+    })
+}
+
+/// Negative durations cannot be represented, they do not make much sense anyway
+fn to_duration(secs: f64) -> Option<Duration> {
+    Duration::try_from_secs_f64(secs).ok()
+}
+
+/// Returns the clockwise rotation of a video stream, in `[0, 360)`.
+/// Older FFMpeg versions expose it as a `rotate` tag, newer ones in the display
+/// matrix side data, counter-clockwise.
+fn rotation(tags: &HashMap<String, String>, side_data_list: &[FFMpegSideData]) -> i32 {
+    side_data_list
+        .iter()
+        .find_map(|side_data| side_data.rotation.map(|rotation| -rotation))
+        .or_else(|| tags.get("rotate").and_then(|rotate| rotate.parse().ok()))
+        .unwrap_or(0)
+        .rem_euclid(360)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! fixture {
+        ($name:literal) => {
+            FFMpegInfos::from_json(include_str!(concat!(
+                "../../tests/fixtures/ffprobe/",
+                $name,
+                ".json"
+            )))
+            .unwrap()
+        };
+    }
+
+    #[test]
+    fn mp4_h264_aac() {
+        let infos = fixture!("mp4_h264_aac");
+        assert_eq!(infos.dimensions(), Some((1920, 1080)));
+        assert_eq!(infos.nb_frames(), Some(300));
+        assert_eq!(infos.duration(), Some(10.01));
+
+        let metadata = infos.metadata();
+        assert_eq!(
+            metadata.creation_time(),
+            Some("2023-06-14T09:12:44.000000Z")
+        );
+        assert_eq!(metadata.format.bit_rate, Some(5116355));
+
+        let video = metadata.video_stream().unwrap();
+        assert_eq!(video.frame_rate, Some(Rational::new(30000, 1001)));
+        assert!(!video.variable_frame_rate);
+        assert_eq!(video.time_base, Some(Rational::new(1, 30000)));
+        assert_eq!(video.display_aspect_ratio, Some(Rational::new(16, 9)));
+        assert_eq!(video.rotation, 90);
+        assert_eq!(video.color_primaries.as_deref(), Some("bt709"));
+
+        let audio = metadata.audio_stream().unwrap();
+        assert_eq!(audio.sample_rate, 44100);
+        assert_eq!(audio.channel_layout.as_deref(), Some("stereo"));
+    }
+
+    #[test]
+    fn webm_vp9_opus() {
+        let infos = fixture!("webm_vp9_opus");
+        assert_eq!(infos.dimensions(), Some((1280, 720)));
+        // No nb_frames nor stream duration in WebM
+        assert_eq!(infos.duration(), Some(12.));
+        assert_eq!(infos.nb_frames(), Some(300));
+
+        let metadata = infos.metadata();
+        let video = metadata.video_stream().unwrap();
+        assert_eq!(video.nb_frames, None);
+        assert_eq!(video.duration, Some(Duration::from_secs(12)));
+        assert_eq!(metadata.audio_stream().unwrap().profile, None);
+        assert_eq!(infos.alpha_decoder(), None);
+    }
+
+    #[test]
+    fn webm_vp9_alpha() {
+        let infos = FFMpegInfos::from_json(
+            r#"{"streams": [{
+                "index": 0, "codec_type": "video", "codec_name": "vp9",
+                "width": 640, "height": 360, "pix_fmt": "yuv420p",
+                "tags": {"alpha_mode": "1", "DURATION": "00:00:02.000000000"}
+            }], "format": {}}"#,
+        )
+        .unwrap();
+        assert_eq!(infos.alpha_decoder(), Some("libvpx-vp9"));
+        assert!(infos.metadata().video_stream().unwrap().has_alpha);
+    }
+
+    #[test]
+    fn mkv_h264_ac3_subtitles_attachment() {
+        let infos = fixture!("mkv_h264_ac3_subtitles_attachment");
+        assert_eq!(infos.streams.len(), 4);
+        assert!(matches!(infos.streams[2], FFMpegStream::Subtitle { .. }));
+        assert!(matches!(infos.streams[3], FFMpegStream::Attachment { .. }));
+        assert_eq!(infos.nb_frames(), Some(1440));
+        assert_eq!(infos.frame_rate(), Some(Rational::new(24000, 1001)));
+
+        let metadata = infos.metadata();
+        assert_eq!(metadata.video_streams.len(), 1);
+        assert_eq!(metadata.audio_streams.len(), 1);
+        let video = metadata.video_stream().unwrap();
+        assert_eq!(video.pixel_format, PixelFormat::Yuv420p10le);
+        assert!(!video.has_alpha);
+        assert_eq!(metadata.audio_stream().unwrap().channels, 6);
+    }
+
+    #[test]
+    fn mov_prores4444_timecode() {
+        let infos = fixture!("mov_prores4444_timecode");
+        assert!(matches!(infos.streams[1], FFMpegStream::Data { .. }));
+        assert_eq!(infos.nb_frames(), Some(48));
+
+        let metadata = infos.metadata();
+        let video = metadata.video_stream().unwrap();
+        assert_eq!(video.pixel_format, PixelFormat::Yuva444p12le);
+        assert!(video.has_alpha);
+        assert_eq!(infos.alpha_decoder(), None);
+        assert_eq!(video.bit_rate, Some(1129012245));
+        assert_eq!(
+            video.tags.get("timecode").map(String::as_str),
+            Some("01:00:00:00")
+        );
+    }
+
+    #[test]
+    fn mov_h264_dropframe_timecode() {
+        // The rounded average rate is within the tolerance, the exact base
+        // rate is the one drop-frame timecodes need
+        let infos = fixture!("mov_h264_dropframe_timecode");
+        assert!(!infos.is_variable_frame_rate());
+        assert_eq!(infos.frame_rate(), Some(Rational::new(30000, 1001)));
+
+        let rate = Rational::new(30000, 1001);
+        let timecode = Timecode::parse("10:00:00;02", rate).unwrap();
+        assert_eq!(infos.timecode(), Some(timecode));
+        let metadata = infos.metadata();
+        let video = metadata.video_stream().unwrap();
+        assert_eq!(video.avg_frame_rate, Some(Rational::new(2997, 100)));
+        assert_eq!(video.timecode, Some(timecode));
+    }
+
+    #[test]
+    fn png_sequence() {
+        let infos = fixture!("png_sequence");
+        assert_eq!(infos.nb_frames(), Some(50));
+
+        let metadata = infos.metadata();
+        // size is reported as `N/A` for image sequences
+        assert_eq!(metadata.format.size, None);
+        assert_eq!(metadata.format.bit_rate, None);
+        assert!(metadata.audio_streams.is_empty());
+    }
+
+    #[test]
+    fn mpegts_h264_aac() {
+        let infos = fixture!("mpegts_h264_aac");
+        assert!(matches!(infos.streams[2], FFMpegStream::Data { .. }));
+        assert_eq!(infos.nb_frames(), Some(300));
+        assert_eq!(infos.timecode(), None);
+        assert_eq!(infos.metadata().video_stream().unwrap().tags.len(), 0);
+    }
+
+    #[test]
+    fn unknown_stream_type() {
+        let infos = FFMpegInfos::from_json(
+            r#"{"streams": [{"index": 0, "codec_type": "unknown"}], "format": {}}"#,
+        )
+        .unwrap();
+        assert!(matches!(infos.streams[0], FFMpegStream::Unknown));
+        assert_eq!(infos.dimensions(), None);
+        assert_eq!(infos.nb_frames(), None);
+    }
+
+    #[test]
+    fn stream_selection() {
+        let mut infos = FFMpegInfos::from_json(
+            r#"{"streams": [
+                {"index": 0, "codec_type": "video", "codec_name": "mjpeg",
+                 "width": 600, "height": 600, "disposition": {"attached_pic": 1}},
+                {"index": 1, "codec_type": "audio", "codec_name": "aac"},
+                {"index": 2, "codec_type": "video", "codec_name": "h264",
+                 "width": 1920, "height": 1080, "avg_frame_rate": "25/1"},
+                {"index": 3, "codec_type": "video", "codec_name": "h264",
+                 "width": 1280, "height": 720, "avg_frame_rate": "50/1"}
+            ], "format": {}}"#,
+        )
+        .unwrap();
+        // The cover art is skipped
+        assert_eq!(infos.video_index(), Some(2));
+        assert_eq!(infos.dimensions(), Some((1920, 1080)));
+
+        infos.select_video_stream(StreamSelector::Index(3)).unwrap();
+        assert_eq!(infos.dimensions(), Some((1280, 720)));
+        assert_eq!(infos.frame_rate(), Some(Rational::new(50, 1)));
+        infos.select_video_stream(StreamSelector::Nth(0)).unwrap();
+        assert_eq!(infos.dimensions(), Some((600, 600)));
+        assert_eq!(infos.frame_rate(), None);
+        assert!(infos.select_video_stream(StreamSelector::Index(1)).is_err());
+
+        let metadata = infos.metadata();
+        assert_eq!(metadata.video_streams.len(), 3);
+        assert!(metadata.video_streams[0].attached_picture);
+        assert_eq!(metadata.video_stream().unwrap().index, 2);
+        assert_eq!(
+            metadata
+                .find_video_stream(StreamSelector::Nth(2))
+                .map(|video| video.index),
+            Some(3)
+        );
+        assert_eq!(
+            metadata
+                .find_audio_stream(StreamSelector::Default)
+                .map(|audio| audio.index),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn variable_frame_rate() {
+        let infos = FFMpegInfos::from_json(
+            r#"{"streams": [{
+                "index": 0, "codec_type": "video", "width": 1080, "height": 1920,
+                "r_frame_rate": "60/1", "avg_frame_rate": "8999/300",
+                "duration": "10.000000"
+            }], "format": {}}"#,
+        )
+        .unwrap();
+        assert!(infos.is_variable_frame_rate());
+        assert_eq!(infos.frame_rate(), Some(Rational::new(8999, 300)));
+        assert_eq!(infos.nb_frames(), Some(299));
+        assert!(infos.metadata().video_stream().unwrap().variable_frame_rate);
+    }
+
+    #[test]
+    fn constant_frame_rate_rounding() {
+        // A constant 29.97 fps whose average is computed from a rounded
+        // duration
+        let infos = FFMpegInfos::from_json(
+            r#"{"streams": [{
+                "index": 0, "codec_type": "video", "width": 1920, "height": 1080,
+                "r_frame_rate": "30000/1001", "avg_frame_rate": "2997/100",
+                "duration": "10.010000"
+            }], "format": {}}"#,
+        )
+        .unwrap();
+        assert!(!infos.is_variable_frame_rate());
+        assert_eq!(infos.frame_rate(), Some(Rational::new(30000, 1001)));
+        assert!(!infos.metadata().video_stream().unwrap().variable_frame_rate);
+
+        assert!(!is_variable_frame_rate("25/1", "1249993/50000"));
+        assert!(is_variable_frame_rate("25/1", "24/1"));
+        assert!(!is_variable_frame_rate("0/0", "25/1"));
+    }
 }
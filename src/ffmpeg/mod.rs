@@ -1,8 +1,11 @@
+mod args;
+use self::args::path_arg;
+
 mod infos;
 pub(super) use self::infos::FFMpegInfos;
 
 mod reader;
-pub(super) use self::reader::FFMpegVideoReader;
+pub(super) use self::reader::{FFMpegVideoReader, Input, InputData, ReadOptions};
 
 mod writer;
-pub(super) use self::writer::FFMpegVideoWriter;
+pub use self::writer::{EncoderSettings, FFMpegVideoWriter};
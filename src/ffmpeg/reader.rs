@@ -1,17 +1,108 @@
+use crate::{Rational, RawPixel};
 use eyre::{bail, eyre, Result};
-use std::io::Read;
-use std::process::ChildStdout;
+use std::io::{Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use std::{
     path::PathBuf,
     process::{Command, Stdio},
 };
 
+/// Kind of input read by FFMpeg
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Input {
+    /// A video file
+    File,
+    /// A numbered image sequence, the path being its pattern
+    ImageSequence { start_number: u64 },
+    /// A single image, repeated for every frame
+    Still,
+}
+
+/// Content of a file written to the input of FFMpeg
+#[derive(Clone)]
+pub(crate) enum InputData {
+    /// Kept in memory, written again each time the file is read
+    Memory(Arc<[u8]>),
+    /// Read from a stream, taken by the first reader
+    Stream(Arc<Mutex<Option<InputStream>>>),
+}
+
+/// A stream, and what was already read from it to probe it
+pub(crate) struct InputStream {
+    head: Vec<u8>,
+    reader: Box<dyn Read + Send>,
+}
+
+impl InputData {
+    pub fn stream(head: Vec<u8>, reader: Box<dyn Read + Send>) -> Self {
+        Self::Stream(Arc::new(Mutex::new(Some(InputStream { head, reader }))))
+    }
+
+    /// Writes the data to the input of FFMpeg, from a thread.
+    /// FFMpeg stops reading once it has enough frames, or when killed, the
+    /// write then fails and the thread ends.
+    fn feed(&self, mut stdin: ChildStdin) -> Result<()> {
+        match self {
+            Self::Memory(data) => {
+                let data = data.clone();
+                std::thread::spawn(move || {
+                    let _ = stdin.write_all(&data);
+                });
+            }
+            Self::Stream(stream) => {
+                let InputStream { head, mut reader } = stream
+                    .lock()
+                    .map_err(|_| eyre!("the input stream is poisoned"))?
+                    .take()
+                    .ok_or(eyre!(
+                        "the clip was read from a stream, which can only be read once"
+                    ))?;
+                std::thread::spawn(move || {
+                    let _ = stdin
+                        .write_all(&head)
+                        .and_then(|_| std::io::copy(&mut reader, &mut stdin));
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for InputData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Memory(data) => write!(f, "InputData::Memory({} bytes)", data.len()),
+            Self::Stream(_) => f.write_str("InputData::Stream"),
+        }
+    }
+}
+
+/// What to read from a file, and how
+#[derive(Debug, Clone)]
+pub(crate) struct ReadOptions<'a> {
+    pub input: Input,
+    pub dimensions: (u32, u32),
+    pub decoder: Option<&'a str>, // decoder to use instead of the default one
+    pub stream: Option<u32>,      // index of the video stream to read
+    pub data: Option<InputData>,  // content of the file, when it is in memory
+    pub start_frame: u64,
+    pub frame_rate: Rational,
+    pub variable_frame_rate: bool,
+    pub max_nb_frames: u32,
+    // Seek exactly to `start_frame`, or only to the keyframe before it
+    pub accurate_seek: bool,
+}
+
 #[derive(Debug)]
 pub(crate) struct FFMpegVideoReader {
+    child: Child, // killed when dropped, to stop decoding
     width: u32,
     height: u32,
     stdout: ChildStdout,
-    pixel_depth: u8,
+    bytes_per_pixel: usize,
     max_nb_frames: u32, // maximum number of frames to read
     current_frame: u32, // when reading, the current frame number handled
 }
@@ -19,27 +110,94 @@ pub(crate) struct FFMpegVideoReader {
 impl FFMpegVideoReader {
     /// Reads a video from a given file.
     /// This methods does not get the video informations from FFMpeg, it uses
-    /// what is given as parameters
-    pub fn from_file(
-        path: &PathBuf,
-        (width, height): (u32, u32),
-        pixel_depth: u8,
-        start: String,
-        max_nb_frames: u32,
-    ) -> Result<Self> {
-        if !path.as_path().is_file() {
+    /// what is given as parameters.
+    /// Frames are converted to the raw pixel format of `P`.
+    pub fn from_file<P: RawPixel>(path: &PathBuf, options: &ReadOptions) -> Result<Self> {
+        let ReadOptions {
+            input,
+            dimensions: (width, height),
+            decoder,
+            stream,
+            data,
+            start_frame,
+            frame_rate,
+            variable_frame_rate,
+            max_nb_frames,
+            accurate_seek,
+        } = options.clone();
+
+        let is_file = matches!(input, Input::ImageSequence { .. }) || path.as_path().is_file();
+        if data.is_none() && !is_file {
             bail!("not a valid file: {:?}", path);
         }
+        // Pipes cannot be seeked, FFMpeg reads them from the start and drops
+        // the frames before the requested one
+        let accurate_seek = accurate_seek || data.is_some();
+        let input_path = match data {
+            Some(_) => "pipe:0".into(),
+            None => super::path_arg(path),
+        };
 
-        let pix_fmt = if pixel_depth == 3 { "rgb24" } else { "rgba" };
-        let start = start.as_ref();
+        // Seek half a frame before the requested one: with accurate seeking,
+        // FFMpeg decodes from the previous keyframe and drops every frame
+        // before the given time, rounding errors on the timestamps must not
+        // make it drop the requested frame nor keep the previous one.
+        // Without it, the output starts at the keyframe before the requested
+        // frame, which only needs to decode a single frame.
+        // Image sequences start directly at the right image, and all the frames
+        // of a still image are the same.
+        let input_options = match (input, start_frame) {
+            (Input::ImageSequence { start_number }, _) => vec![
+                "-f".to_string(),
+                "image2".into(),
+                "-framerate".into(),
+                frame_rate.to_string(),
+                "-start_number".into(),
+                (start_number + start_frame).to_string(),
+            ],
+            (Input::Still, _) => vec![
+                "-loop".to_string(),
+                "1".into(),
+                "-framerate".into(),
+                frame_rate.to_string(),
+            ],
+            (Input::File, 0) => Vec::new(),
+            (Input::File, _) if accurate_seek => {
+                let time = frame_rate.frame_time(start_frame) - frame_rate.frame_time(1) / 2;
+                vec!["-accurate_seek".to_string(), "-ss".into(), seek_time(time)]
+            }
+            (Input::File, _) => vec![
+                "-noaccurate_seek".to_string(),
+                "-ss".into(),
+                seek_time(frame_rate.frame_time(start_frame)),
+            ],
+        };
+        let decoder = decoder
+            .map(|decoder| vec!["-c:v", decoder])
+            .unwrap_or_default();
+        // Without it, FFMpeg picks the stream with the highest resolution
+        let map = stream
+            .map(|index| vec!["-map".to_string(), format!("0:{}", index)])
+            .unwrap_or_default();
+        // Frames are output as decoded, unless the source has a variable frame
+        // rate: in this case they are duplicated/dropped to get a constant one.
+        let fps_mode = if variable_frame_rate {
+            vec![
+                "-fps_mode".to_string(),
+                "cfr".into(),
+                "-r".into(),
+                frame_rate.to_string(),
+            ]
+        } else {
+            vec!["-fps_mode".to_string(), "passthrough".into()]
+        };
 
         let mut output = Command::new("ffmpeg")
+            .args(input_options)
+            .args(decoder)
+            .arg("-i")
+            .arg(input_path)
             .args([
-                "-ss",
-                start,
-                "-i",
-                path.to_str().ok_or(eyre!("path is not utf8 string"))?,
                 "-loglevel",
                 "error",
                 "-f",
@@ -49,46 +207,124 @@ impl FFMpegVideoReader {
                 "-sws_flags",
                 "bicubic", // resize algo
                 "-pix_fmt",
-                pix_fmt,
+                P::PIX_FMT,
+            ])
+            .args(map)
+            .args(fps_mode)
+            .args([
+                "-frames:v",
+                max_nb_frames.to_string().as_ref(),
                 "-vcodec",
                 "rawvideo",
                 "-",
             ])
-            .stdin(Stdio::null())
+            .stdin(if data.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .spawn()
             .map_err(|err| eyre!("unable to get output: {:?}", err))?;
 
         let stdout = output.stdout.take().expect("cannot get stdout");
+        if let (Some(data), Some(stdin)) = (data, output.stdin.take()) {
+            if let Err(err) = data.feed(stdin) {
+                let _ = output.kill();
+                let _ = output.wait();
+                return Err(err);
+            }
+        }
 
         Ok(Self {
+            child: output,
             stdout,
             width,
             height,
-            pixel_depth,
+            bytes_per_pixel: P::bytes_per_pixel(),
             max_nb_frames,
             current_frame: 0,
         })
     }
 
-    /// Read a frame until the data is exhausted
-    pub fn read_frame(&mut self) -> Result<Option<Vec<u8>>> {
+    /// Reads the next frame into the given buffer, which must hold exactly
+    /// one frame.
+    /// Returns false once all the frames have been read.
+    pub fn read_frame_into(&mut self, buffer: &mut [u8]) -> Result<bool> {
         // If we have hit the frame limit, stop reading
         if self.current_frame >= self.max_nb_frames {
-            return Ok(None);
+            return Ok(false);
         }
 
-        let frame_size = self.width as usize * self.height as usize * self.pixel_depth as usize;
-        let mut buffer = vec![0; frame_size];
+        let frame_size = self.width as usize * self.height as usize * self.bytes_per_pixel;
+        if buffer.len() != frame_size {
+            bail!(
+                "frame buffer is {} bytes, expected {}",
+                buffer.len(),
+                frame_size
+            );
+        }
 
-        // FIXME: not sure read_exact is what we want here
-        self.stdout
-            .read_exact(&mut buffer)
-            .map_err(|err| eyre!("failed to read: {:?}", err))?;
+        let mut read = 0;
+        while read < buffer.len() {
+            match self.stdout.read(&mut buffer[read..]) {
+                // The stream can end before `max_nb_frames`, which may be
+                // estimated, but not in the middle of a frame
+                Ok(0) if read == 0 => return self.end_of_stream(),
+                Ok(0) => bail!(
+                    "frame {} is truncated, {} bytes of {}",
+                    self.current_frame,
+                    read,
+                    frame_size
+                ),
+                Ok(count) => read += count,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(err) => bail!("failed to read: {:?}", err),
+            }
+        }
 
         self.current_frame += 1;
 
-        Ok(Some(buffer))
+        Ok(true)
+    }
+
+    /// Checks that FFMpeg succeeded once its output ended, as it stops
+    /// writing frames on decoding errors
+    fn end_of_stream(&mut self) -> Result<bool> {
+        self.max_nb_frames = self.current_frame;
+
+        // FFMpeg is exiting, its logs are complete
+        let mut logs = String::new();
+        if let Some(mut stderr) = self.child.stderr.take() {
+            let _ = stderr.read_to_string(&mut logs);
+        }
+        let status = self
+            .child
+            .wait()
+            .map_err(|err| eyre!("unable to wait for ffmpeg: {:?}", err))?;
+        if !status.success() {
+            bail!(
+                "ffmpeg failed after {} frames ({}): {}",
+                self.current_frame,
+                status,
+                logs.trim()
+            );
+        }
+
+        Ok(false)
     }
 }
+
+impl Drop for FFMpegVideoReader {
+    fn drop(&mut self) {
+        // FFMpeg would keep decoding until the pipe is closed, stop it now
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Formats a time for the `-ss` option
+fn seek_time(time: Duration) -> String {
+    format!("{}.{:06}", time.as_secs(), time.subsec_micros())
+}
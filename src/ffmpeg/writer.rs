@@ -1,14 +1,82 @@
+use crate::{Frame, FrameSink, Rational, RawPixel};
 use eyre::eyre;
 use std::{
-    io::{BufRead, BufReader, Write},
-    path::PathBuf,
-    process::{ChildStderr, ChildStdin, Command, Stdio},
+    ffi::OsStr,
+    io::{BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStderr, ChildStdin, ChildStdout, Command, Stdio},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
 };
 
+/// Where the encoded video goes
 #[derive(Debug)]
-pub(crate) struct FFMpegVideoWriter {
+enum Output {
+    File(PathBuf),
+    /// Copied from the output of FFMpeg by a thread
+    Writer {
+        bytes: Arc<AtomicU64>,
+        thread: Option<JoinHandle<std::io::Result<()>>>,
+    },
+}
+
+/// How FFMpeg encodes the video, as in a pipeline config, ie
+/// `{"codec": "libx265", "preset": "slow", "crf": 24, "pix_fmt": "yuv420p"}`.
+/// Only the codec is set by default, the other settings are left to the
+/// defaults of the encoder.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EncoderSettings {
+    /// FFMpeg encoder, `libx264` by default
+    pub codec: String,
+    pub preset: Option<String>,
+    /// Constant rate factor, the quality for encoders which support it
+    pub crf: Option<u8>,
+    /// Pixel format of the encoded video, ie `yuv420p` for wide support
+    pub pix_fmt: Option<String>,
+}
+
+impl Default for EncoderSettings {
+    fn default() -> Self {
+        Self {
+            codec: "libx264".to_string(),
+            preset: None,
+            crf: None,
+            pix_fmt: None,
+        }
+    }
+}
+
+impl EncoderSettings {
+    /// Returns the FFMpeg output options
+    fn args(&self) -> Vec<String> {
+        let mut args = vec!["-vcodec".to_string(), self.codec.clone()];
+        if let Some(preset) = &self.preset {
+            args.extend(["-preset".to_string(), preset.clone()]);
+        }
+        if let Some(crf) = self.crf {
+            args.extend(["-crf".to_string(), crf.to_string()]);
+        }
+        if let Some(pix_fmt) = &self.pix_fmt {
+            args.extend(["-pix_fmt".to_string(), pix_fmt.clone()]);
+        }
+
+        args
+    }
+}
+
+/// Encodes raw frames to a video file.
+/// The video must be finished: dropping the writer before stops FFMpeg and
+/// leaves an incomplete video.
+#[derive(Debug)]
+pub struct FFMpegVideoWriter {
+    output: Output,
+    child: Child,
     stderr: BufReader<ChildStderr>,
-    stdin: ChildStdin,
+    stdin: Option<ChildStdin>, // closed when finished
 }
 
 impl FFMpegVideoWriter {
@@ -16,17 +84,125 @@ impl FFMpegVideoWriter {
     // XXX: take the pix_fmt as str for now, we must infer this fro the
     // Pixel in the frame.
     pub fn to_file(
-        path: &PathBuf,
+        path: impl AsRef<Path>,
         (width, height): (u32, u32),
-        fps: f32,
+        frame_rate: Rational,
         pix_fmt: &'static str,
     ) -> eyre::Result<Self> {
-        // Assumes a lot of things:
-        // libx264 codec, medium encoding preset, rgb24 pixel format
+        Self::to_file_with_encoder(
+            path,
+            (width, height),
+            frame_rate,
+            pix_fmt,
+            &EncoderSettings::default(),
+        )
+    }
+
+    /// Encodes the video to a file with the given encoder settings
+    pub fn to_file_with_encoder(
+        path: impl AsRef<Path>,
+        (width, height): (u32, u32),
+        frame_rate: Rational,
+        pix_fmt: &str,
+        encoder: &EncoderSettings,
+    ) -> eyre::Result<Self> {
+        let mut command = Self::spawn(
+            (width, height),
+            frame_rate,
+            pix_fmt,
+            encoder,
+            &["-y".into(), super::path_arg(path.as_ref())],
+            Stdio::null(),
+        )?;
 
-        let mut command = Command::new("ffmpeg")
+        let stdin = command.stdin.take().expect("cannot get stdin");
+        let stderr = BufReader::new(command.stderr.take().expect("cannot get stderr"));
+
+        Ok(FFMpegVideoWriter {
+            output: Output::File(path.as_ref().to_path_buf()),
+            child: command,
+            stdin: Some(stdin),
+            stderr,
+        })
+    }
+
+    /// Encodes the video to `writer`, ie a pipe or a socket, in the given
+    /// FFMpeg format. Formats must support being written to a stream:
+    /// `mpegts`, `matroska`, or `mp4` which is then fragmented.
+    /// The writer is used by a thread until the video is finished.
+    pub fn to_writer(
+        writer: impl Write + Send + 'static,
+        format: &str,
+        (width, height): (u32, u32),
+        frame_rate: Rational,
+        pix_fmt: &'static str,
+    ) -> eyre::Result<Self> {
+        Self::to_writer_with_encoder(
+            writer,
+            format,
+            (width, height),
+            frame_rate,
+            pix_fmt,
+            &EncoderSettings::default(),
+        )
+    }
+
+    /// Encodes the video to `writer` with the given encoder settings
+    pub fn to_writer_with_encoder(
+        writer: impl Write + Send + 'static,
+        format: &str,
+        (width, height): (u32, u32),
+        frame_rate: Rational,
+        pix_fmt: &str,
+        encoder: &EncoderSettings,
+    ) -> eyre::Result<Self> {
+        let mut args = vec!["-f", format];
+        if matches!(format, "mp4" | "mov" | "ipod") {
+            // The index of a regular MP4 is written at the start, once the
+            // whole file is known, which cannot be done in a stream
+            args.extend(["-movflags", "frag_keyframe+empty_moov+default_base_moof"]);
+        }
+        args.push("pipe:1");
+        let mut command = Self::spawn(
+            (width, height),
+            frame_rate,
+            pix_fmt,
+            encoder,
+            &args,
+            Stdio::piped(),
+        )?;
+
+        let stdin = command.stdin.take().expect("cannot get stdin");
+        let stderr = BufReader::new(command.stderr.take().expect("cannot get stderr"));
+        let stdout = command.stdout.take().expect("cannot get stdout");
+        let bytes = Arc::new(AtomicU64::new(0));
+        let thread = {
+            let bytes = bytes.clone();
+            std::thread::spawn(move || copy_output(stdout, writer, &bytes))
+        };
+
+        Ok(FFMpegVideoWriter {
+            output: Output::Writer {
+                bytes,
+                thread: Some(thread),
+            },
+            child: command,
+            stdin: Some(stdin),
+            stderr,
+        })
+    }
+
+    /// Starts FFMpeg, reading raw frames on its input
+    fn spawn<S: AsRef<OsStr>>(
+        (width, height): (u32, u32),
+        frame_rate: Rational,
+        pix_fmt: &str,
+        encoder: &EncoderSettings,
+        output_args: &[S],
+        stdout: Stdio,
+    ) -> eyre::Result<Child> {
+        Command::new("ffmpeg")
             .args([
-                "-y",
                 "-loglevel",
                 "error",
                 "-f",
@@ -36,48 +212,181 @@ impl FFMpegVideoWriter {
                 "-pix_fmt",
                 pix_fmt,
                 "-r",
-                format!("{:.2}", fps).as_str(),
+                frame_rate.to_string().as_str(),
                 "-an",
                 "-i",
                 "-",
-                "-vcodec",
-                "libx264",
-                "-preset",
-                "medium",
-                // "-threads", "X"
-                path.to_str().ok_or(eyre!("path is not a utf8 string"))?,
             ])
+            .args(encoder.args())
+            .args(output_args)
             .stdin(Stdio::piped())
-            .stdout(Stdio::null())
+            .stdout(stdout)
             .stderr(Stdio::piped())
             .spawn()
-            .map_err(|err| eyre!("unable to spawn command: {:?}", err))?;
-
-        let stdin = command.stdin.take().expect("cannot get stdin");
-        let stderr = BufReader::new(command.stderr.take().expect("cannot get stderr"));
-
-        Ok(FFMpegVideoWriter { stdin, stderr })
+            .map_err(|err| eyre!("unable to spawn command: {:?}", err))
     }
 
     /// Write a frame to the output file
     pub fn write_frame(&mut self, frame: &[u8]) -> eyre::Result<()> {
-        self.stdin.write_all(frame).or_else(|err| {
-            // Got an error, read stderr
-            let mut stderr = String::new();
-            loop {
-                let read = self
-                    .stderr
-                    .read_line(&mut stderr)
-                    .map_err(|err| eyre!("unable to read stderr: {:?}", err))?;
-                if read == 0 {
-                    break;
-                }
-            }
-            Err(eyre::eyre!(
+        let stdin = self
+            .stdin
+            .as_mut()
+            .ok_or(eyre!("cannot write a frame, the writer is finished"))?;
+        if let Err(err) = stdin.write_all(frame) {
+            eyre::bail!(
                 "unable to write: {:?}, stderr: {:?}",
                 err,
-                stderr
-            ))
-        })
+                self.read_stderr()?
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Closes the input of FFMpeg, and waits for it to finish writing the
+    /// file
+    pub fn finish(&mut self) -> eyre::Result<()> {
+        drop(self.stdin.take());
+        let status = self
+            .child
+            .wait()
+            .map_err(|err| eyre!("unable to wait for ffmpeg: {:?}", err))?;
+        if !status.success() {
+            eyre::bail!(
+                "ffmpeg failed ({}), stderr: {:?}",
+                status,
+                self.read_stderr()?
+            );
+        }
+
+        self.join_output()
+    }
+
+    /// Stops FFMpeg without finishing the video, and deletes the file.
+    /// What was already given to a writer cannot be taken back.
+    pub fn abort(&mut self) -> eyre::Result<()> {
+        drop(self.stdin.take());
+        let _ = self.child.kill();
+        self.child
+            .wait()
+            .map_err(|err| eyre!("unable to wait for ffmpeg: {:?}", err))?;
+
+        match &self.output {
+            Output::File(path) => match std::fs::remove_file(path) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                    eyre::bail!("cannot delete {:?}: {}", path, err)
+                }
+                _ => Ok(()),
+            },
+            Output::Writer { .. } => {
+                let _ = self.join_output();
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the size of the video written so far
+    pub fn bytes_written(&self) -> Option<u64> {
+        match &self.output {
+            Output::File(path) => std::fs::metadata(path).ok().map(|metadata| metadata.len()),
+            Output::Writer { bytes, .. } => Some(bytes.load(Ordering::Relaxed)),
+        }
+    }
+
+    /// Waits for the whole video to be copied to the writer
+    fn join_output(&mut self) -> eyre::Result<()> {
+        let Output::Writer { thread, .. } = &mut self.output else {
+            return Ok(());
+        };
+        match thread.take().map(JoinHandle::join) {
+            Some(Ok(Err(err))) => eyre::bail!("unable to write the video: {}", err),
+            Some(Err(_)) => eyre::bail!("the thread writing the video panicked"),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reads what FFMpeg output on stderr
+    fn read_stderr(&mut self) -> eyre::Result<String> {
+        let mut stderr = String::new();
+        loop {
+            let read = self
+                .stderr
+                .read_line(&mut stderr)
+                .map_err(|err| eyre!("unable to read stderr: {:?}", err))?;
+            if read == 0 {
+                break;
+            }
+        }
+
+        Ok(stderr)
+    }
+}
+
+/// Copies the output of FFMpeg to the writer, counting the bytes
+fn copy_output(
+    mut stdout: ChildStdout,
+    mut writer: impl Write,
+    bytes: &AtomicU64,
+) -> std::io::Result<()> {
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = match stdout.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        writer.write_all(&buffer[..read])?;
+        bytes.fetch_add(read as u64, Ordering::Relaxed);
+    }
+
+    writer.flush()
+}
+
+impl Drop for FFMpegVideoWriter {
+    fn drop(&mut self) {
+        // Not finished, FFMpeg would otherwise keep running and become a
+        // zombie once done
+        if self.stdin.is_some() {
+            let _ = self.child.kill();
+        }
+        let _ = self.child.wait();
+    }
+}
+
+impl<P: RawPixel> FrameSink<P> for FFMpegVideoWriter {
+    fn write(&mut self, frame: &Frame<P>) -> eyre::Result<()> {
+        self.write_frame(frame.as_bytes())
+    }
+
+    fn finish(&mut self) -> eyre::Result<()> {
+        FFMpegVideoWriter::finish(self)
+    }
+
+    fn abort(&mut self) -> eyre::Result<()> {
+        FFMpegVideoWriter::abort(self)
+    }
+
+    fn bytes_written(&self) -> Option<u64> {
+        FFMpegVideoWriter::bytes_written(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoder_args() {
+        assert_eq!(EncoderSettings::default().args(), ["-vcodec", "libx264"]);
+
+        let encoder: EncoderSettings =
+            serde_json::from_str(r#"{"codec": "libx265", "crf": 24, "pix_fmt": "yuv420p"}"#)
+                .unwrap();
+        assert_eq!(
+            encoder.args(),
+            ["-vcodec", "libx265", "-crf", "24", "-pix_fmt", "yuv420p"]
+        );
+        assert!(serde_json::from_str::<EncoderSettings>(r#"{"bitrate": "2M"}"#).is_err());
     }
 }
@@ -0,0 +1,91 @@
+use image::{ImageBuffer, Pixel};
+
+/// Width of a glyph, in font pixels
+pub(crate) const GLYPH_WIDTH: u32 = 3;
+/// Height of a glyph, in font pixels
+pub(crate) const GLYPH_HEIGHT: u32 = 5;
+
+/// Returns the rows of a glyph of the built-in 3x5 bitmap font, the highest
+/// bit of each row being the leftmost pixel.
+/// Only digits and the characters used in times and timecodes are available.
+fn glyph(c: char) -> Option<[u8; 5]> {
+    Some(match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        ';' => [0b000, 0b010, 0b000, 0b010, 0b100],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        ' ' => [0; 5],
+        _ => return None,
+    })
+}
+
+/// Returns the (width, height) in pixels of the text drawn at `scale`
+pub(crate) fn text_size(text: &str, scale: u32) -> (u32, u32) {
+    let len = text.chars().count() as u32;
+    let width = (len * (GLYPH_WIDTH + 1)).saturating_sub(1);
+
+    (width * scale, GLYPH_HEIGHT * scale)
+}
+
+/// Draws the text with its top left corner at (x, y), each font pixel being a
+/// `scale`x`scale` square.
+/// If a background is given, a box with a margin of one font pixel is filled
+/// behind the text. Unknown characters are drawn as blanks, and what is out
+/// of the image is cropped.
+pub(crate) fn draw_text<P: Pixel>(
+    image: &mut ImageBuffer<P, Vec<P::Subpixel>>,
+    (x, y): (u32, u32),
+    scale: u32,
+    text: &str,
+    foreground: P,
+    background: Option<P>,
+) {
+    let (width, height) = text_size(text, scale);
+    let mut put_pixel = |px: u32, py: u32, pixel: P| {
+        if px < image.width() && py < image.height() {
+            image.put_pixel(px, py, pixel);
+        }
+    };
+
+    if let Some(background) = background {
+        let x = x.saturating_sub(scale);
+        let y = y.saturating_sub(scale);
+        for py in y..y + height + 2 * scale {
+            for px in x..x + width + 2 * scale {
+                put_pixel(px, py, background);
+            }
+        }
+    }
+
+    for (index, c) in text.chars().enumerate() {
+        let rows = glyph(c).unwrap_or_default();
+        let left = x + index as u32 * (GLYPH_WIDTH + 1) * scale;
+        for (row, bits) in rows.into_iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - column)) == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        put_pixel(
+                            left + column * scale + dx,
+                            y + row as u32 * scale + dy,
+                            foreground,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,13 +1,125 @@
-use image::{ImageBuffer, Pixel};
-use std::marker::PhantomData;
+use crate::{FramePool, Rational, RawPixel};
+use image::{
+    buffer::PixelsMut, EncodableLayout, ImageBuffer, ImageFormat, Pixel, PixelWithColorType,
+    Primitive,
+};
+use rayon::{iter::IndexedParallelIterator, iter::ParallelIterator, slice::ParallelSliceMut};
+use std::{
+    path::Path,
+    slice::ChunksExactMut,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+/// Identifies the clip frames were read from. Subclips share the id of the
+/// clip they were cut from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ClipId(u64);
+
+impl ClipId {
+    /// Returns a new unique id
+    pub(crate) fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Where a frame comes from, and when it is shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameInfo {
+    /// Index of the frame in its source
+    pub index: u64,
+    /// Presentation timestamp, in `time_base` units
+    pub pts: i64,
+    /// Duration of the frame, in `time_base` units
+    pub duration: i64,
+    pub time_base: Rational,
+    /// Clip the frame was read from, None for generated frames
+    pub source: Option<ClipId>,
+}
+
+impl FrameInfo {
+    /// Returns the presentation time of the frame, negative timestamps
+    /// being clamped to zero
+    pub fn time(&self) -> Duration {
+        self.ticks_time(self.pts)
+    }
+
+    /// Returns the duration of the frame
+    pub fn duration_time(&self) -> Duration {
+        self.ticks_time(self.duration)
+    }
+
+    fn ticks_time(&self, value: i64) -> Duration {
+        let divisor = self.time_base.den() as i128;
+        let nanos =
+            (value as i128 * self.time_base.num() as i128 * 1_000_000_000 + divisor / 2) / divisor;
+
+        Duration::from_nanos(nanos.max(0) as u64)
+    }
+}
+
+impl Default for FrameInfo {
+    fn default() -> Self {
+        Self {
+            index: 0,
+            pts: 0,
+            duration: 0,
+            time_base: Rational::new(1, 1),
+            source: None,
+        }
+    }
+}
+
+/// Computes the infos of the frames of a constant frame rate source
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FrameTiming {
+    pub(crate) start_frame: u64, // index of the first frame read
+    pub(crate) frame_rate: Rational,
+    pub(crate) time_base: Rational,
+    pub(crate) start_pts: i64, // timestamp of the first frame of the source
+    pub(crate) source: Option<ClipId>,
+}
+
+impl FrameTiming {
+    /// Returns the infos of the `offset`th frame read
+    pub(crate) fn info(&self, offset: u64) -> FrameInfo {
+        let index = self.start_frame + offset;
+
+        FrameInfo {
+            index,
+            pts: self.pts(index),
+            duration: self.pts(index + 1) - self.pts(index),
+            time_base: self.time_base,
+            source: self.source,
+        }
+    }
+
+    /// Returns the timestamp of the frame at the given index, rounded to the
+    /// nearest `time_base` unit
+    fn pts(&self, index: u64) -> i64 {
+        let (rate, base) = (self.frame_rate, self.time_base);
+        let divisor = rate.num() as i128 * base.num() as i128;
+        if divisor <= 0 {
+            return self.start_pts;
+        }
+        let ticks =
+            (index as i128 * rate.den() as i128 * base.den() as i128 + divisor / 2) / divisor;
+
+        self.start_pts + ticks as i64
+    }
+}
 
 pub struct Frame<P>
 where
     P: Pixel,
 {
     data: ImageBuffer<P, Vec<P::Subpixel>>,
-    width: u32,
-    height: u32,
+    info: FrameInfo,
+    pool: Option<FramePool<P::Subpixel>>, // where the buffer goes when dropped
 }
 
 impl<P> Frame<P>
@@ -20,38 +132,216 @@ where
 
         Self {
             data,
-            width,
-            height,
+            info: FrameInfo::default(),
+            pool: None,
         }
     }
 
-    /// Returns the raw buffer
-    /// TODO: this is not ideal, we can do better
-    pub fn as_raw(&self) -> &Vec<<P as Pixel>::Subpixel> {
+    /// Returns the same frame, its buffer going to the pool when dropped
+    pub fn with_pool(mut self, pool: FramePool<P::Subpixel>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Returns the pool the buffer of the frame goes to when dropped
+    pub(crate) fn pool(&self) -> Option<&FramePool<P::Subpixel>> {
+        self.pool.as_ref()
+    }
+
+    /// Returns the same frame, with the given infos
+    pub fn with_info(mut self, info: FrameInfo) -> Self {
+        self.info = info;
+        self
+    }
+
+    /// Returns where the frame comes from, and when it is shown
+    pub fn info(&self) -> &FrameInfo {
+        &self.info
+    }
+
+    /// Returns the subpixels of the frame, row by row
+    pub fn as_raw(&self) -> &[P::Subpixel] {
         self.data.as_raw()
     }
 
+    /// Returns the frame as the raw bytes FFMpeg reads and writes, without
+    /// copying
+    pub fn as_bytes(&self) -> &[u8]
+    where
+        P: RawPixel,
+    {
+        P::as_raw_bytes(self.data.as_raw())
+    }
+
+    /// Returns the frame as raw bytes, to be modified in place
+    pub fn as_bytes_mut(&mut self) -> &mut [u8]
+    where
+        P: RawPixel,
+    {
+        P::as_raw_bytes_mut(&mut self.data)
+    }
+
+    /// Returns the (width, height) of the frame
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.data.dimensions()
+    }
+
     /// Returns the underlying image::ImageBuffer, so we can use the image::imageops
     /// methods.
-    pub(crate) fn image(&self) -> &ImageBuffer<P, Vec<P::Subpixel>> {
+    pub fn image(&self) -> &ImageBuffer<P, Vec<P::Subpixel>> {
         &self.data
     }
 
-    /// Transforms the current frame using the given function.
-    /// Transformation is applied on pixels, so the format of the frame cannot
-    /// change.
-    pub fn transform<F, O>(self, func: F) -> Frame<O>
+    /// Returns the underlying image::ImageBuffer, to draw on the frame or
+    /// modify it with the image::imageops methods
+    pub fn image_mut(&mut self) -> &mut ImageBuffer<P, Vec<P::Subpixel>> {
+        &mut self.data
+    }
+
+    /// Returns an iterator on the pixels of the frame, to modify them in
+    /// place
+    pub fn pixels_mut(&mut self) -> PixelsMut<'_, P> {
+        self.data.pixels_mut()
+    }
+
+    /// Returns an iterator on the rows of the frame, as subpixels, to modify
+    /// them in place
+    pub fn rows_mut(&mut self) -> ChunksExactMut<'_, P::Subpixel> {
+        let row = self.row_len();
+        self.data.chunks_exact_mut(row)
+    }
+
+    /// Calls `func` with the (x, y) coordinates of each pixel, and the pixel
+    /// to modify
+    pub fn for_each_pixel_mut<F>(&mut self, mut func: F)
+    where
+        F: FnMut(u32, u32, &mut P),
+    {
+        for (x, y, pixel) in self.data.enumerate_pixels_mut() {
+            func(x, y, pixel);
+        }
+    }
+
+    /// Calls `func` with the index of each row, and its subpixels to modify.
+    /// Rows are processed in parallel.
+    pub fn par_rows_mut<F>(&mut self, func: F)
+    where
+        F: Fn(u32, &mut [P::Subpixel]) + Send + Sync,
+        P::Subpixel: Send,
+    {
+        let row = self.row_len();
+        self.data
+            .par_chunks_exact_mut(row)
+            .enumerate()
+            .for_each(|(y, subpixels)| func(y as u32, subpixels));
+    }
+
+    /// Number of subpixels in a row, at least 1 for chunks
+    fn row_len(&self) -> usize {
+        (self.data.width() as usize * P::CHANNEL_COUNT as usize).max(1)
+    }
+
+    /// Saves the frame as an image, the format is deduced from the extension
+    /// of the path (ie `png`, `jpg`).
+    /// Not every format supports every pixel type: JPEG has no alpha channel
+    /// nor 16 bits support.
+    pub fn save(&self, path: impl AsRef<Path>) -> eyre::Result<()>
+    where
+        P: PixelWithColorType,
+        [P::Subpixel]: EncodableLayout,
+    {
+        self.data
+            .save(path.as_ref())
+            .map_err(|err| eyre::eyre!("cannot save frame to {:?}: {}", path.as_ref(), err))
+    }
+
+    /// Saves the frame as an image in the given format
+    pub fn save_with_format(&self, path: impl AsRef<Path>, format: ImageFormat) -> eyre::Result<()>
+    where
+        P: PixelWithColorType,
+        [P::Subpixel]: EncodableLayout,
+    {
+        self.data
+            .save_with_format(path.as_ref(), format)
+            .map_err(|err| eyre::eyre!("cannot save frame to {:?}: {}", path.as_ref(), err))
+    }
+
+    /// Maps each pixel to a new pixel type with the same subpixel type, ie RGB
+    /// to gray or RGB to RGBA. The dimensions and infos of the frame are kept.
+    /// The buffer of the frame is reused when the new pixels have as many
+    /// channels or less, otherwise one is taken from the pool of the frame.
+    pub fn transform<F, O>(mut self, func: F) -> Frame<O>
     where
         F: Fn(&P) -> O,
         O: Pixel<Subpixel = P::Subpixel>,
     {
-        let mut buffer = Vec::with_capacity(self.data.len());
+        let (from, to) = (P::CHANNEL_COUNT as usize, O::CHANNEL_COUNT as usize);
+        let (width, height) = self.data.dimensions();
+        let len = width as usize * height as usize;
+        let pool = self.pool.take();
+        let mut source = std::mem::take(&mut self.data).into_raw();
+
+        let buffer = if to <= from {
+            // Each pixel is written before the ones read next
+            for index in 0..len {
+                let pixel = func(P::from_slice(&source[index * from..(index + 1) * from]));
+                source[index * to..(index + 1) * to].copy_from_slice(pixel.channels());
+            }
+            source.truncate(len * to);
+            source
+        } else {
+            let mut buffer = match &pool {
+                Some(pool) => pool.take(len * to),
+                None => vec![P::Subpixel::DEFAULT_MIN_VALUE; len * to],
+            };
+            for (pixel, channels) in source.chunks_exact(from).zip(buffer.chunks_exact_mut(to)) {
+                channels.copy_from_slice(func(P::from_slice(pixel)).channels());
+            }
+            if let Some(pool) = &pool {
+                pool.recycle(source);
+            }
+            buffer
+        };
+
+        let frame = Frame::from_vec(buffer, (width, height)).with_info(self.info);
+        match pool {
+            Some(pool) => frame.with_pool(pool),
+            None => frame,
+        }
+    }
+
+    /// Transforms the pixels of the frame in place
+    pub fn transform_in_place<F>(mut self, func: F) -> Self
+    where
+        F: Fn(&P) -> P,
+    {
+        for pixel in self.data.pixels_mut() {
+            *pixel = func(pixel);
+        }
+
+        self
+    }
+
+    /// Crops the frame in place, to the rectangle of the given size at
+    /// (x, y). As `image::imageops::crop`, the rectangle is clamped to the
+    /// frame.
+    pub fn crop(mut self, x: u32, y: u32, width: u32, height: u32) -> Self {
+        let (frame_width, frame_height) = self.data.dimensions();
+        let (x, y) = (x.min(frame_width), y.min(frame_height));
+        let (width, height) = (width.min(frame_width - x), height.min(frame_height - y));
+        let channels = P::CHANNEL_COUNT as usize;
+        let (row, stride) = (width as usize * channels, frame_width as usize * channels);
 
-        for pixel in self.data.pixels() {
-            buffer.extend(func(pixel).channels());
+        // Rows only move towards the start of the buffer
+        let mut data = std::mem::take(&mut self.data).into_raw();
+        for line in 0..height as usize {
+            let start = (y as usize + line) * stride + x as usize * channels;
+            data.copy_within(start..start + row, line * row);
         }
+        data.truncate(row * height as usize);
 
-        Frame::from_vec(buffer, (self.width, self.height))
+        self.data = ImageBuffer::from_raw(width, height, data).expect("cropped buffer");
+        self
     }
 
     /// Map the given frame with the given function.
@@ -65,44 +355,200 @@ where
     }
 }
 
+impl<P> Drop for Frame<P>
+where
+    P: Pixel,
+{
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.take() {
+            pool.recycle(std::mem::take(&mut self.data).into_raw());
+        }
+    }
+}
+
 // ----------------------------------------------------------------------------
 
-pub struct IterFrame<P> {
+/// Number of buffers kept by the pool of an `IterFrame`, enough for the few
+/// frames alive at once in a pipeline
+const POOL_CAPACITY: usize = 4;
+
+/// The error that stopped reading frames, an `IterFrame` ending as if all
+/// the frames were read.
+/// Clones share the same error, so that one can be kept to check it once the
+/// frames went through effects, ie with `Render::read_error`.
+#[derive(Debug, Clone, Default)]
+pub struct ReadError(Arc<Mutex<Option<eyre::Report>>>);
+
+impl ReadError {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the error, if reading failed
+    pub fn take(&self) -> Option<eyre::Report> {
+        self.lock().take()
+    }
+
+    pub(crate) fn set(&self, err: eyre::Report) {
+        self.lock().get_or_insert(err);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<eyre::Report>> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+pub struct IterFrame<P>
+where
+    P: Pixel,
+{
     reader: crate::ffmpeg::FFMpegVideoReader,
     width: u32,
     height: u32,
     nb_frames: usize,
-    _phantom: PhantomData<P>,
+    timing: FrameTiming,
+    current_frame: u64,
+    pool: FramePool<P::Subpixel>,
+    error: ReadError,
 }
 
-impl<P> IterFrame<P> {
+impl<P> IterFrame<P>
+where
+    P: Pixel,
+{
     pub(crate) fn new(
         reader: crate::ffmpeg::FFMpegVideoReader,
         (width, height): (u32, u32),
         nb_frames: usize,
+        timing: FrameTiming,
     ) -> Self {
         Self {
             reader,
             width,
             height,
             nb_frames,
-            _phantom: PhantomData,
+            timing,
+            current_frame: 0,
+            pool: FramePool::new(POOL_CAPACITY),
+            error: ReadError::new(),
         }
     }
+
+    /// Reads the frames into the buffers of the given pool, ie to share it
+    /// between several clips
+    pub fn with_pool(mut self, pool: FramePool<P::Subpixel>) -> Self {
+        self.pool = pool;
+        self
+    }
+
+    /// Records a read error in `error`, ie to share it between several clips
+    pub fn with_read_error(mut self, error: ReadError) -> Self {
+        self.error = error;
+        self
+    }
+
+    /// Returns the error that stopped reading, checked once the frames are
+    /// consumed
+    pub fn read_error(&self) -> ReadError {
+        self.error.clone()
+    }
 }
 
-impl<P: Pixel<Subpixel = u8>> Iterator for IterFrame<P> {
+impl<P: RawPixel> Iterator for IterFrame<P> {
     type Item = Frame<P>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        // FIXME: here we silently ignore errors, we might want to change that
-        self.reader
-            .read_frame()
-            .ok()?
-            .map(|raw_frame| Frame::from_vec(raw_frame, (self.width, self.height)))
+        let mut frame = self.pool.frame::<P>((self.width, self.height));
+        match self.reader.read_frame_into(frame.as_bytes_mut()) {
+            Ok(true) => {}
+            Ok(false) => return None,
+            Err(err) => {
+                let err = err.wrap_err(format!("cannot read frame {}", self.current_frame));
+                self.error.set(err);
+                return None;
+            }
+        }
+        let info = self.timing.info(self.current_frame);
+        self.current_frame += 1;
+
+        Some(frame.with_info(info))
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
         (self.nb_frames, Some(self.nb_frames))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Luma, Rgb, Rgba};
+
+    #[test]
+    fn in_place() {
+        let frame = Frame::<Rgb<u8>>::from_vec((0..36).collect(), (4, 3));
+        let pointer = frame.as_raw().as_ptr();
+
+        let frame = frame.crop(1, 1, 2, 5);
+        assert_eq!(frame.image().dimensions(), (2, 2));
+        assert_eq!(
+            frame.as_raw(),
+            &[15, 16, 17, 18, 19, 20, 27, 28, 29, 30, 31, 32]
+        );
+        assert_eq!(frame.as_raw().as_ptr(), pointer);
+
+        let frame = frame.transform(|pixel| Luma([pixel[0]]));
+        assert_eq!(frame.as_raw(), &[15, 18, 27, 30]);
+        assert_eq!(frame.as_raw().as_ptr(), pointer);
+
+        // More channels, the buffer comes from the pool
+        let pool = FramePool::new(2);
+        let frame = frame.with_pool(pool.clone());
+        let frame = frame.transform(|pixel| Rgba([pixel[0], 0, 0, 255]));
+        assert_eq!(&frame.as_raw()[..4], &[15, 0, 0, 255]);
+        assert_eq!(pool.len(), 1);
+        drop(frame);
+        assert_eq!(pool.len(), 2);
+
+        let frame = Frame::<Rgb<u16>>::from_vec(vec![0x0102; 3], (1, 1));
+        assert_eq!(frame.as_bytes(), 0x0102u16.to_ne_bytes().repeat(3));
+    }
+
+    #[test]
+    fn mutate() {
+        let mut frame = Frame::<Rgb<u8>>::from_vec(vec![0; 12], (2, 2));
+        for (y, row) in frame.rows_mut().enumerate() {
+            row[0] = y as u8 + 1;
+        }
+        for pixel in frame.pixels_mut() {
+            pixel[2] = 9;
+        }
+        assert_eq!(frame.as_raw(), &[1, 0, 9, 0, 0, 9, 2, 0, 9, 0, 0, 9]);
+
+        frame.par_rows_mut(|y, row| row[1] = 10 * y as u8);
+        frame.for_each_pixel_mut(|x, _, pixel| pixel[2] = x as u8);
+        assert_eq!(frame.as_raw(), &[1, 0, 0, 0, 0, 1, 2, 10, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn save() {
+        let dir = tempfile::tempdir().unwrap();
+        let frame = Frame::<Rgb<u8>>::from_vec((0..24).collect(), (4, 2));
+        let path = dir.path().join("frame.png");
+        frame.save(&path).unwrap();
+        let image = image::open(&path).unwrap().into_rgb8();
+        assert_eq!(image.as_raw(), frame.as_raw());
+
+        let path = dir.path().join("frame.jpg");
+        frame.save(&path).unwrap();
+        assert_eq!(image::open(&path).unwrap().into_rgb8().dimensions(), (4, 2));
+
+        // JPEG has no alpha channel
+        let frame = Frame::<Rgba<u8>>::from_vec(vec![0; 32], (4, 2));
+        assert!(frame
+            .save_with_format(dir.path().join("alpha.jpg"), ImageFormat::Jpeg)
+            .is_err());
+    }
+}
@@ -0,0 +1,495 @@
+use crate::{font, frame::FrameTiming, Frame, Position, Rational, RawPixel, Timecode};
+use std::{marker::PhantomData, time::Duration};
+
+/// A color with normalized components, in `[0, 1]`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color {
+    pub const BLACK: Self = Self::new(0.0, 0.0, 0.0, 1.0);
+    pub const WHITE: Self = Self::new(1.0, 1.0, 1.0, 1.0);
+    pub const TRANSPARENT: Self = Self::new(0.0, 0.0, 0.0, 0.0);
+
+    pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// Creates an opaque color from 8 bits components
+    pub fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self::new(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0)
+    }
+
+    /// Returns the color at `t` (in `[0, 1]`) between `self` and `other`
+    fn mix(self, other: Self, t: f32) -> Self {
+        let t = t.clamp(0.0, 1.0);
+        let mix = |from: f32, to: f32| from + (to - from) * t;
+
+        Self::new(
+            mix(self.r, other.r),
+            mix(self.g, other.g),
+            mix(self.b, other.b),
+            mix(self.a, other.a),
+        )
+    }
+
+    /// Appends the raw bytes of the color in the pixel type `P`.
+    /// Gray pixel types get the luma of the color (Rec. 709).
+    fn push_raw<P: RawPixel>(&self, raw: &mut Vec<u8>) {
+        let luma = 0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b;
+        let channels: &[f32] = match P::COLOR_MODEL {
+            "Y" => &[luma],
+            "YA" => &[luma, self.a],
+            "RGBA" => &[self.r, self.g, self.b, self.a],
+            _ => &[self.r, self.g, self.b],
+        };
+
+        for channel in channels {
+            let channel = channel.clamp(0.0, 1.0);
+            match std::mem::size_of::<P::Subpixel>() {
+                1 => raw.push((channel * 255.0).round() as u8),
+                2 => raw.extend(((channel * 65535.0).round() as u16).to_ne_bytes()),
+                _ => raw.extend(channel.to_ne_bytes()),
+            }
+        }
+    }
+}
+
+/// What a `Generator` draws
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    /// A single color
+    Solid(Color),
+    /// A gradient from one side of the frame to the other, the angle being
+    /// in degrees clockwise (0 goes from left to right)
+    LinearGradient { from: Color, to: Color, angle: f32 },
+    /// A gradient from the center of the frame to its corners
+    RadialGradient { inner: Color, outer: Color },
+    /// SMPTE color bars
+    ColorBars,
+    /// Squares of `size` pixels, alternating two colors
+    Checkerboard { size: u32, colors: (Color, Color) },
+    /// The frame number and its timecode burnt in, with a marker moving from
+    /// left to right every second to make dropped or repeated frames visible
+    Counter {
+        foreground: Color,
+        background: Color,
+        /// Timecode of the first frame, 00:00:00:00 if not set
+        start: Option<Timecode>,
+    },
+    /// Random pixels, the same for a given seed and frame index
+    Noise { seed: u64, gray: bool },
+}
+
+/// Iterator on synthetic frames, which needs no input file: title cards,
+/// padding, test patterns.
+#[derive(Debug, Clone)]
+pub struct Generator<P> {
+    pattern: Pattern,
+    dimensions: (u32, u32),
+    frame_rate: Rational,
+    nb_frames: u64,
+    current_frame: u64,
+    _phantom: PhantomData<P>,
+}
+
+impl<P> Generator<P> {
+    /// Creates a generator of frames of the given (width, height), lasting
+    /// `length` (a number of frames, or a duration).
+    pub fn new(
+        pattern: Pattern,
+        dimensions: (u32, u32),
+        frame_rate: Rational,
+        length: impl Into<Position>,
+    ) -> Self {
+        Self {
+            pattern,
+            dimensions,
+            frame_rate,
+            nb_frames: length.into().to_frame(frame_rate),
+            current_frame: 0,
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.dimensions
+    }
+
+    pub fn frame_rate(&self) -> Rational {
+        self.frame_rate
+    }
+
+    /// Returns the number of frames generated in total
+    pub fn nb_frames(&self) -> u64 {
+        self.nb_frames
+    }
+
+    /// Returns the duration of the generated frames
+    pub fn duration(&self) -> Duration {
+        self.frame_rate.frame_time(self.nb_frames)
+    }
+
+    /// Returns the frame at the given index
+    pub fn frame(&self, index: u64) -> Frame<P>
+    where
+        P: RawPixel,
+    {
+        // Timestamps count frames
+        let time_base = match self.frame_rate.is_zero() {
+            true => Rational::new(1, 1),
+            false => self.frame_rate.recip(),
+        };
+        let timing = FrameTiming {
+            start_frame: 0,
+            frame_rate: self.frame_rate,
+            time_base,
+            start_pts: 0,
+            source: None,
+        };
+
+        self.draw(index).with_info(timing.info(index))
+    }
+
+    /// Draws the frame at the given index
+    fn draw(&self, index: u64) -> Frame<P>
+    where
+        P: RawPixel,
+    {
+        let (width, height) = self.dimensions;
+        let mut raw = Vec::with_capacity(width as usize * height as usize * P::bytes_per_pixel());
+
+        match &self.pattern {
+            Pattern::Solid(color) => {
+                color.push_raw::<P>(&mut raw);
+                raw = raw.repeat(width as usize * height as usize);
+            }
+            Pattern::LinearGradient { from, to, angle } => {
+                let (sin, cos) = angle.to_radians().sin_cos();
+                let extent = (width as f32 * cos).abs() + (height as f32 * sin).abs();
+                self.fill(&mut raw, |x, y| {
+                    from.mix(*to, (x * cos + y * sin) / extent + 0.5)
+                });
+            }
+            Pattern::RadialGradient { inner, outer } => {
+                let radius = (width as f32).hypot(height as f32) / 2.0;
+                self.fill(&mut raw, |x, y| inner.mix(*outer, x.hypot(y) / radius));
+            }
+            Pattern::ColorBars => {
+                self.fill(&mut raw, |x, y| {
+                    color_bar((x / width as f32 + 0.5) * 7.0, y / height as f32 + 0.5)
+                });
+            }
+            Pattern::Checkerboard { size, colors } => {
+                let size = (*size).max(1);
+                for y in 0..height {
+                    for x in 0..width {
+                        let color = if (x / size + y / size).is_multiple_of(2) {
+                            colors.0
+                        } else {
+                            colors.1
+                        };
+                        color.push_raw::<P>(&mut raw);
+                    }
+                }
+            }
+            Pattern::Counter {
+                foreground,
+                background,
+                start,
+            } => return self.counter(index, *foreground, *background, *start),
+            Pattern::Noise { seed, gray } => {
+                let mut rng = SplitMix64(seed ^ index.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+                for _ in 0..width * height {
+                    let bits = rng.next();
+                    let channel = |shift: u32| ((bits >> shift) & 0xffff) as f32 / 65535.0;
+                    let color = if *gray {
+                        let luma = channel(0);
+                        Color::new(luma, luma, luma, 1.0)
+                    } else {
+                        Color::new(channel(0), channel(16), channel(32), 1.0)
+                    };
+                    color.push_raw::<P>(&mut raw);
+                }
+            }
+        }
+
+        Frame::from_vec(P::from_raw_bytes(raw), self.dimensions)
+    }
+
+    /// Fills the frame, calling `color` with the coordinates of the center of
+    /// each pixel, relative to the center of the frame
+    fn fill(&self, raw: &mut Vec<u8>, color: impl Fn(f32, f32) -> Color)
+    where
+        P: RawPixel,
+    {
+        let (width, height) = self.dimensions;
+        for y in 0..height {
+            for x in 0..width {
+                color(
+                    x as f32 + 0.5 - width as f32 / 2.0,
+                    y as f32 + 0.5 - height as f32 / 2.0,
+                )
+                .push_raw::<P>(raw);
+            }
+        }
+    }
+
+    /// Draws the frame number and timecode, centered, and the moving marker
+    fn counter(
+        &self,
+        index: u64,
+        foreground: Color,
+        background: Color,
+        start: Option<Timecode>,
+    ) -> Frame<P>
+    where
+        P: RawPixel,
+    {
+        let (width, height) = self.dimensions;
+        let to_pixel = |color: Color| {
+            let mut raw = Vec::new();
+            color.push_raw::<P>(&mut raw);
+            *P::from_slice(&P::from_raw_bytes(raw))
+        };
+        let (foreground, background) = (to_pixel(foreground), to_pixel(background));
+        let mut frame = Frame::from_vec(
+            background
+                .channels()
+                .repeat(width as usize * height as usize),
+            self.dimensions,
+        );
+
+        let timecode = start
+            .and_then(|start| start.checked_add(index))
+            .or_else(|| Timecode::from_frame(index, self.frame_rate, false).ok())
+            .map(|timecode| timecode.to_string())
+            .unwrap_or_default();
+        let lines = [format!("{:06}", index), timecode];
+
+        // Lines take about half of the frame width
+        let scale = (width / 2 / font::text_size(&lines[1], 1).0.max(1)).max(1);
+        let line_height = font::text_size("0", scale).1 + 2 * scale;
+        let top = (height / 2).saturating_sub(line_height);
+        for (line_index, line) in lines.iter().enumerate() {
+            let (line_width, _) = font::text_size(line, scale);
+            font::draw_text(
+                frame.image_mut(),
+                (
+                    (width.saturating_sub(line_width)) / 2,
+                    top + line_index as u32 * line_height,
+                ),
+                scale,
+                line,
+                foreground,
+                None,
+            );
+        }
+
+        // Marker going through the frame width once per second
+        let frames_per_second = self.frame_rate.frames_in(Duration::from_secs(1)).max(1);
+        let marker = (height / 16).max(1);
+        let x = (width.saturating_sub(marker)) as u64 * (index % frames_per_second)
+            / frames_per_second.saturating_sub(1).max(1);
+        for dy in 0..marker {
+            // Nothing to draw in an empty frame
+            let Some(y) = height.checked_sub(1 + dy) else {
+                break;
+            };
+            for dx in 0..marker {
+                let x = x as u32 + dx;
+                if x < width {
+                    frame.image_mut().put_pixel(x, y, foreground);
+                }
+            }
+        }
+
+        frame
+    }
+}
+
+impl<P: RawPixel> Iterator for Generator<P> {
+    type Item = Frame<P>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.current_frame >= self.nb_frames {
+            return None;
+        }
+
+        let frame = self.frame(self.current_frame);
+        self.current_frame += 1;
+
+        Some(frame)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = (self.nb_frames - self.current_frame) as usize;
+        (len, Some(len))
+    }
+}
+
+impl<P: RawPixel> ExactSizeIterator for Generator<P> {}
+
+/// Returns the SMPTE color bar color at `x` (in bar widths, in `[0, 7]`) and
+/// `y` (relative to the height, in `[0, 1]`)
+fn color_bar(x: f32, y: f32) -> Color {
+    const BLACK: Color = Color::new(0.075, 0.075, 0.075, 1.0);
+    const BARS: [Color; 7] = [
+        Color::new(0.75, 0.75, 0.75, 1.0),
+        Color::new(0.75, 0.75, 0.0, 1.0),
+        Color::new(0.0, 0.75, 0.75, 1.0),
+        Color::new(0.0, 0.75, 0.0, 1.0),
+        Color::new(0.75, 0.0, 0.75, 1.0),
+        Color::new(0.75, 0.0, 0.0, 1.0),
+        Color::new(0.0, 0.0, 0.75, 1.0),
+    ];
+    let bar = (x as usize).min(6);
+
+    if y < 2.0 / 3.0 {
+        BARS[bar]
+    } else if y < 0.75 {
+        // Reversed blue bars
+        if bar.is_multiple_of(2) {
+            BARS[6 - bar]
+        } else {
+            BLACK
+        }
+    } else {
+        match x {
+            x if x < 1.25 => Color::new(0.0, 0.129, 0.298, 1.0), // -I
+            x if x < 2.5 => Color::WHITE,
+            x if x < 3.75 => Color::new(0.196, 0.0, 0.416, 1.0), // +Q
+            x if x < 5.0 => BLACK,
+            // PLUGE: below black, black, above black
+            x if x < 5.0 + 1.0 / 3.0 => Color::new(0.035, 0.035, 0.035, 1.0),
+            x if x < 5.0 + 2.0 / 3.0 => BLACK,
+            x if x < 6.0 => Color::new(0.114, 0.114, 0.114, 1.0),
+            _ => BLACK,
+        }
+    }
+}
+
+/// SplitMix64 pseudo-random number generator
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Luma, Rgb, Rgba};
+
+    const RATE: Rational = Rational::new(25, 1);
+
+    #[test]
+    fn solid() {
+        let mut generator =
+            Generator::<Rgba<u8>>::new(Pattern::Solid(Color::rgb(255, 0, 128)), (4, 2), RATE, 3);
+        assert_eq!(generator.len(), 3);
+        let frame = generator.next().unwrap();
+        assert_eq!(frame.as_raw(), &[255, 0, 128, 255].repeat(8));
+        assert_eq!(generator.count(), 2);
+
+        let generator = Generator::<Luma<u16>>::new(
+            Pattern::Solid(Color::WHITE),
+            (4, 2),
+            RATE,
+            Duration::from_secs(2),
+        );
+        assert_eq!(generator.nb_frames(), 50);
+        assert_eq!(generator.frame(0).as_raw(), &vec![65535; 8]);
+    }
+
+    #[test]
+    fn patterns() {
+        let gradient = Pattern::LinearGradient {
+            from: Color::BLACK,
+            to: Color::WHITE,
+            angle: 0.0,
+        };
+        let frame = Generator::<Luma<u8>>::new(gradient, (4, 1), RATE, 1).frame(0);
+        assert_eq!(frame.as_raw(), &[32, 96, 159, 223]);
+
+        let radial = Pattern::RadialGradient {
+            inner: Color::WHITE,
+            outer: Color::BLACK,
+        };
+        let frame = Generator::<Luma<u8>>::new(radial, (64, 64), RATE, 1).frame(0);
+        let image = frame.image();
+        assert!(image.get_pixel(32, 32)[0] > 250);
+        assert!(image.get_pixel(0, 0)[0] < 5);
+
+        let frame = Generator::<Rgb<u8>>::new(Pattern::ColorBars, (70, 40), RATE, 1).frame(0);
+        let image = frame.image();
+        assert_eq!(image.get_pixel(5, 5), &Rgb([191, 191, 191]));
+        assert_eq!(image.get_pixel(15, 5), &Rgb([191, 191, 0]));
+        assert_eq!(image.get_pixel(65, 5), &Rgb([0, 0, 191]));
+        assert_eq!(image.get_pixel(5, 28), &Rgb([0, 0, 191]));
+        assert_eq!(image.get_pixel(20, 35), &Rgb([255, 255, 255]));
+
+        let checkerboard = Pattern::Checkerboard {
+            size: 2,
+            colors: (Color::BLACK, Color::WHITE),
+        };
+        let frame = Generator::<Luma<u8>>::new(checkerboard, (4, 4), RATE, 1).frame(0);
+        assert_eq!(
+            frame.as_raw(),
+            &[0, 0, 255, 255, 0, 0, 255, 255, 255, 255, 0, 0, 255, 255, 0, 0]
+        );
+    }
+
+    #[test]
+    fn counter() {
+        let counter = Pattern::Counter {
+            foreground: Color::WHITE,
+            background: Color::BLACK,
+            start: Some(Timecode::parse("01:00:00:00", RATE).unwrap()),
+        };
+        let frames =
+            Generator::<Luma<u8>>::new(counter.clone(), (128, 72), RATE, 3).collect::<Vec<_>>();
+        assert_ne!(frames[0].as_raw(), frames[1].as_raw());
+        assert_ne!(frames[1].as_raw(), frames[2].as_raw());
+        assert!(frames[0].as_raw().contains(&255));
+        // The marker is in the bottom left corner on the first frame
+        assert_eq!(frames[0].image().get_pixel(0, 71), &Luma([255]));
+        assert_eq!(frames[1].image().get_pixel(0, 71), &Luma([0]));
+
+        // Empty frames
+        for dimensions in [(0, 0), (128, 0), (0, 72)] {
+            let frame = Generator::<Luma<u8>>::new(counter.clone(), dimensions, RATE, 1)
+                .next()
+                .unwrap();
+            assert_eq!(frame.image().dimensions(), dimensions);
+        }
+    }
+
+    #[test]
+    fn noise() {
+        let noise = |seed| Pattern::Noise { seed, gray: false };
+        let generator = Generator::<Rgb<u8>>::new(noise(42), (16, 16), RATE, 2);
+        let first = generator.frame(0);
+        assert_eq!(first.as_raw(), generator.frame(0).as_raw());
+        assert_ne!(first.as_raw(), generator.frame(1).as_raw());
+        let other = Generator::<Rgb<u8>>::new(noise(43), (16, 16), RATE, 2).frame(0);
+        assert_ne!(first.as_raw(), other.as_raw());
+
+        let gray = Pattern::Noise {
+            seed: 42,
+            gray: true,
+        };
+        let frame = Generator::<Rgb<u8>>::new(gray, (16, 16), RATE, 1).frame(0);
+        assert!(frame.image().pixels().all(|pixel| pixel[0] == pixel[1]));
+    }
+}
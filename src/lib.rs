@@ -1,10 +1,59 @@
 mod clip;
 pub use self::clip::Clip;
 
+mod metadata;
+pub use self::metadata::{AudioStreamInfo, ClipMetadata, FormatInfo, VideoStreamInfo};
+
+mod stream;
+pub use self::stream::StreamSelector;
+
+mod pixel;
+pub use self::pixel::{PixelFormat, RawPixel};
+
+mod position;
+pub use self::position::Position;
+
+mod rational;
+pub use self::rational::Rational;
+
+mod timecode;
+pub use self::timecode::Timecode;
+
+mod thumbnails;
+pub use self::thumbnails::Thumbnails;
+
+mod sprite;
+pub use self::sprite::{ContactSheet, Sprite, SpriteCue};
+
+mod font;
+
+mod sequence;
+
 mod frame;
-pub use self::frame::Frame;
+pub use self::frame::{ClipId, Frame, FrameInfo, ReadError};
+
+mod pool;
+pub use self::pool::FramePool;
 
 mod ffmpeg;
+pub use self::ffmpeg::{EncoderSettings, FFMpegVideoWriter};
+
+mod generate;
+pub use self::generate::{Color, Generator, Pattern};
+
+mod sink;
+pub use self::sink::{FrameSink, ImageSequenceWriter, Y4mWriter};
+
+mod animated;
+pub use self::animated::{Animated, Easing, Interpolate};
 
 mod effects;
-pub use self::effects::EffectsExt;
+pub use self::effects::{
+    Apply, Crop, Effect, EffectsExt, FrameContext, Grayscale, KenBurns, Rect, Resize,
+};
+
+mod render;
+pub use self::render::{CancellationToken, Cancelled, Progress, Render};
+
+mod pipeline;
+pub use self::pipeline::{EffectConfig, OutputConfig, PipelineConfig};
@@ -0,0 +1,442 @@
+use clap::{Parser, Subcommand};
+use image::Rgb;
+use movieru::{
+    Clip, ClipMetadata, ContactSheet, Frame, FrameSink, OutputConfig, PipelineConfig, Position,
+    Progress, Rational, ReadError, Render, Timecode,
+};
+use std::{
+    io::{IsTerminal, Write},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+/// Video editing with FFMpeg
+///
+/// Positions are frames (`120`), seconds (`4.5s`) or timecodes
+/// (`00:00:04:12`). Outputs are encoded by FFMpeg, except `.y4m` files and
+/// image sequences (`frame_%05d.png`). An input `-` is read from stdin.
+#[derive(Parser)]
+#[command(name = "movieru", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Prints the metadata of a media file
+    Probe {
+        input: PathBuf,
+        /// Prints JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Cuts a part of a video
+    Cut {
+        input: PathBuf,
+        #[arg(short, long)]
+        output: PathBuf,
+        /// First frame of the cut
+        #[arg(long, default_value = "0")]
+        start: String,
+        /// Frame following the cut, the end of the video by default
+        #[arg(long, conflicts_with = "duration")]
+        end: Option<String>,
+        /// Duration of the cut
+        #[arg(long)]
+        duration: Option<String>,
+    },
+    /// Concatenates videos of the same frame rate, scaled to the dimensions
+    /// of the first one
+    Concat {
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Saves a thumbnail, or a contact sheet of thumbnails
+    Thumb {
+        input: PathBuf,
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Position of the thumbnail, the middle of the video by default
+        #[arg(long, conflicts_with = "sheet")]
+        at: Option<String>,
+        /// Saves a contact sheet instead of a single thumbnail
+        #[arg(long)]
+        sheet: bool,
+        /// Number of thumbnails of the sheet
+        #[arg(long, default_value_t = 100)]
+        count: usize,
+        /// Number of thumbnails per row of the sheet
+        #[arg(long, default_value_t = 10)]
+        columns: u32,
+        /// Width of the thumbnails of the sheet
+        #[arg(long, default_value_t = 160)]
+        width: u32,
+        /// Draws the time of each thumbnail of the sheet
+        #[arg(long)]
+        labels: bool,
+        /// Also writes the WebVTT track of the sheet
+        #[arg(long, requires = "sheet")]
+        vtt: Option<PathBuf>,
+    },
+    /// Runs a pipeline config (JSON or TOML) on a video
+    Render {
+        pipeline: PathBuf,
+        #[arg(short, long)]
+        input: PathBuf,
+        /// Overrides the output of the pipeline
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+fn main() -> eyre::Result<()> {
+    match Cli::parse().command {
+        Command::Probe { input, json } => {
+            // Audio files, without any video stream, can be probed too
+            let metadata = if input.as_os_str() == "-" {
+                ClipMetadata::from_reader(std::io::stdin())?
+            } else {
+                ClipMetadata::from_file(input)?
+            };
+            if json {
+                println!("{}", serde_json::to_string_pretty(&metadata)?);
+            } else {
+                print_table(&metadata);
+            }
+        }
+        Command::Cut {
+            input,
+            output,
+            start,
+            end,
+            duration,
+        } => {
+            let clip = open(input)?;
+            let start = parse_position(&start, clip.frame_rate())?;
+            let clip = match (end, duration) {
+                (Some(end), _) => {
+                    clip.subclip_range(start, parse_position(&end, clip.frame_rate())?)?
+                }
+                (None, Some(duration)) => {
+                    clip.subclip_for(start, parse_position(&duration, clip.frame_rate())?)?
+                }
+                (None, None) => clip.subclip_range(start, clip.len() as u64)?,
+            };
+
+            let mut sink = sink(output, clip.dimensions(), clip.frame_rate())?;
+            let frames = clip.iter_frames()?;
+            let read_error = frames.read_error();
+            write_frames(frames, None, read_error, sink.as_mut())?;
+        }
+        Command::Concat { inputs, output } => {
+            let clips = inputs
+                .iter()
+                .cloned()
+                .map(open)
+                .collect::<eyre::Result<Vec<_>>>()?;
+            let (dimensions, frame_rate) = (clips[0].dimensions(), clips[0].frame_rate());
+            for (input, clip) in inputs.iter().zip(&clips) {
+                if clip.frame_rate() != frame_rate {
+                    eyre::bail!(
+                        "{:?} is at {} fps, the first video at {} fps",
+                        input,
+                        clip.frame_rate(),
+                        frame_rate
+                    );
+                }
+            }
+            let total = clips.iter().map(|clip| clip.len() as u64).sum();
+
+            // Each video is decoded once the previous one is done
+            let mut error = None;
+            let read_error = ReadError::new();
+            let frames = clips
+                .into_iter()
+                .map_while(
+                    |clip| match clip.with_dimensions(dimensions).iter_frames() {
+                        Ok(frames) => Some(frames.with_read_error(read_error.clone())),
+                        Err(err) => {
+                            error = Some(err);
+                            None
+                        }
+                    },
+                )
+                .flatten();
+            let mut sink = sink(output, dimensions, frame_rate)?;
+            write_frames(frames, Some(total), read_error.clone(), sink.as_mut())?;
+            if let Some(err) = error {
+                return Err(err);
+            }
+        }
+        Command::Thumb {
+            input,
+            output,
+            at,
+            sheet,
+            count,
+            columns,
+            width,
+            labels,
+            vtt,
+        } => {
+            // A contact sheet reads the video once per thumbnail
+            let clip = if sheet && input.as_os_str() == "-" {
+                Clip::from_reader_buffered(std::io::stdin())?
+            } else {
+                open(input)?
+            };
+            if sheet {
+                let sprite = ContactSheet {
+                    count,
+                    columns,
+                    thumbnail_width: width,
+                    labels,
+                    ..Default::default()
+                }
+                .generate(&clip)?;
+                match vtt {
+                    Some(vtt) => sprite.save(&output, vtt)?,
+                    None => sprite.image.save(&output)?,
+                }
+            } else {
+                let position = match at {
+                    Some(at) => parse_position(&at, clip.frame_rate())?,
+                    None => Position::Frame(clip.len() as u64 / 2),
+                };
+                clip.frame_at(position)?.save(&output)?;
+            }
+        }
+        Command::Render {
+            pipeline,
+            input,
+            output,
+        } => {
+            let mut config = PipelineConfig::from_file(pipeline)?;
+            if let Some(output) = output {
+                config.output.path = output;
+            }
+
+            let clip = open(input)?;
+            with_progress(None, |render| config.render(&clip, render))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses frames (`120`), seconds (`4.5s`) or a timecode (`00:00:04:12`)
+fn parse_position(position: &str, frame_rate: Rational) -> eyre::Result<Position> {
+    if position.contains([':', ';']) {
+        return Ok(Timecode::parse(position, frame_rate)?.into());
+    }
+    if let Some(seconds) = position.strip_suffix('s') {
+        let seconds = seconds
+            .parse::<f64>()
+            .ok()
+            .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+            .ok_or(eyre::eyre!("invalid number of seconds {:?}", position))?;
+        return Ok(Duration::from_secs_f64(seconds).into());
+    }
+
+    position
+        .parse::<u64>()
+        .map(Position::Frame)
+        .map_err(|_| eyre::eyre!("invalid position {:?}", position))
+}
+
+/// Opens a video file, or streams it from stdin for `-`
+fn open(input: PathBuf) -> eyre::Result<Clip<Rgb<u8>>> {
+    if input.as_os_str() == "-" {
+        Clip::from_reader(std::io::stdin())
+    } else {
+        Clip::from_file(input)
+    }
+}
+
+fn sink(
+    path: PathBuf,
+    dimensions: (u32, u32),
+    frame_rate: Rational,
+) -> eyre::Result<Box<dyn FrameSink<Rgb<u8>>>> {
+    OutputConfig {
+        path,
+        frame_rate: None,
+        encoder: Default::default(),
+    }
+    .sink(dimensions, frame_rate)
+}
+
+/// Writes the frames, showing the progress. `total` is needed when the
+/// frames do not know their number.
+fn write_frames(
+    frames: impl Iterator<Item = Frame<Rgb<u8>>>,
+    total: Option<u64>,
+    read_error: ReadError,
+    sink: &mut dyn FrameSink<Rgb<u8>>,
+) -> eyre::Result<()> {
+    with_progress(total, |render| {
+        render.read_error(read_error).run(frames, sink)
+    })
+}
+
+/// Runs a render, showing its progress
+fn with_progress(
+    total: Option<u64>,
+    run: impl FnOnce(Render<'_>) -> eyre::Result<()>,
+) -> eyre::Result<()> {
+    let mut bar = ProgressBar::new();
+    let mut render = Render::new().on_progress(|progress| bar.update(progress));
+    if let Some(total) = total {
+        render = render.total_frames(total);
+    }
+    run(render)?;
+    bar.finish();
+
+    Ok(())
+}
+
+/// Progress bar on stderr, when it is a terminal
+struct ProgressBar {
+    last: Option<Progress>,
+    drawn: Option<Instant>,
+    visible: bool,
+}
+
+impl ProgressBar {
+    const WIDTH: usize = 40;
+
+    fn new() -> Self {
+        Self {
+            last: None,
+            drawn: None,
+            visible: std::io::stderr().is_terminal(),
+        }
+    }
+
+    fn update(&mut self, progress: &Progress) {
+        self.last = Some(*progress);
+        // Redraws at most 10 times per second
+        if self
+            .drawn
+            .is_none_or(|drawn| drawn.elapsed() >= Duration::from_millis(100))
+        {
+            self.draw();
+        }
+    }
+
+    fn finish(&mut self) {
+        self.draw();
+        if self.visible {
+            eprintln!();
+        }
+    }
+
+    fn draw(&mut self) {
+        let Some(progress) = self.last.filter(|_| self.visible) else {
+            return;
+        };
+
+        self.drawn = Some(Instant::now());
+        let done = progress.frames_done;
+        let mut line = match progress.total_frames {
+            Some(total) if total > 0 => {
+                let filled = Self::WIDTH * done.min(total) as usize / total as usize;
+                format!(
+                    "[{}{}] {}/{} frames",
+                    "#".repeat(filled),
+                    " ".repeat(Self::WIDTH - filled),
+                    done,
+                    total
+                )
+            }
+            _ => format!("{} frames", done),
+        };
+        line += &format!(", {:.1} fps", progress.fps);
+        if let Some(eta) = progress.eta {
+            line += &format!(", {}s left", eta.as_secs());
+        }
+        if let Some(bytes) = progress.bytes_written {
+            line += &format!(", {:.1} MB", bytes as f64 / 1e6);
+        }
+        let mut stderr = std::io::stderr();
+        let _ = write!(stderr, "\r{}\x1b[K", line);
+        let _ = stderr.flush();
+    }
+}
+
+fn print_table(metadata: &ClipMetadata) {
+    let format = &metadata.format;
+    println!(
+        "{:<12}{} ({})",
+        "format", format.format_name, format.format_long_name
+    );
+    if let Some(duration) = format.duration {
+        println!("{:<12}{:.3}s", "duration", duration.as_secs_f64());
+    }
+    if let Some(size) = format.size {
+        println!("{:<12}{} bytes", "size", size);
+    }
+    if let Some(bit_rate) = format.bit_rate {
+        println!("{:<12}{} kb/s", "bit rate", bit_rate / 1000);
+    }
+
+    for video in &metadata.video_streams {
+        let frame_rate = video
+            .avg_frame_rate
+            .or(video.frame_rate)
+            .map(|rate| format!(", {} fps", rate))
+            .unwrap_or_default();
+        let nb_frames = video
+            .nb_frames
+            .map(|nb_frames| format!(", {} frames", nb_frames))
+            .unwrap_or_default();
+        println!(
+            "{:<12}video {} {}x{} {}{}{}{}",
+            format!("stream #{}", video.index),
+            video.codec_name,
+            video.width,
+            video.height,
+            video.pixel_format,
+            frame_rate,
+            nb_frames,
+            if video.attached_picture {
+                " (cover art)"
+            } else {
+                ""
+            }
+        );
+    }
+    for audio in &metadata.audio_streams {
+        println!(
+            "{:<12}audio {} {} Hz, {} channels",
+            format!("stream #{}", audio.index),
+            audio.codec_name,
+            audio.sample_rate,
+            audio.channels
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions() {
+        let rate = Rational::new(25, 1);
+        assert_eq!(parse_position("120", rate).unwrap(), Position::Frame(120));
+        assert_eq!(
+            parse_position("4.5s", rate).unwrap(),
+            Position::Time(Duration::from_millis(4500))
+        );
+        assert_eq!(
+            parse_position("00:00:04:12", rate).unwrap(),
+            Position::Timecode(Timecode::new(0, 0, 4, 12, rate, false).unwrap())
+        );
+        assert!(parse_position("-1s", rate).is_err());
+        assert!(parse_position("soon", rate).is_err());
+    }
+}
@@ -0,0 +1,164 @@
+use crate::{ffmpeg, PixelFormat, Rational, StreamSelector, Timecode};
+use std::{collections::HashMap, io::Read, path::PathBuf, time::Duration};
+
+/// Metadata of a media file, as reported by ffprobe.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ClipMetadata {
+    /// Informations about the container
+    pub format: FormatInfo,
+    /// All the video streams, in the order they appear in the container
+    pub video_streams: Vec<VideoStreamInfo>,
+    /// All the audio streams, in the order they appear in the container
+    pub audio_streams: Vec<AudioStreamInfo>,
+}
+
+impl ClipMetadata {
+    /// Probes a media file, which may have no video stream, ie an audio file
+    pub fn from_file(path: impl Into<PathBuf>) -> eyre::Result<Self> {
+        Ok(ffmpeg::FFMpegInfos::from_file(&path.into())?.metadata())
+    }
+
+    /// Probes a media file read from `reader`, ie stdin
+    pub fn from_reader(reader: impl Read + Send) -> eyre::Result<Self> {
+        Ok(ffmpeg::FFMpegInfos::from_reader(reader)?.metadata())
+    }
+
+    /// Returns the first video stream that is not a cover art, if any
+    pub fn video_stream(&self) -> Option<&VideoStreamInfo> {
+        self.find_video_stream(StreamSelector::Default)
+    }
+
+    /// Returns the first audio stream, if any
+    pub fn audio_stream(&self) -> Option<&AudioStreamInfo> {
+        self.audio_streams.first()
+    }
+
+    /// Returns the video stream matching the selector
+    pub fn find_video_stream(&self, selector: StreamSelector) -> Option<&VideoStreamInfo> {
+        let streams = self
+            .video_streams
+            .iter()
+            .map(|video| (video.index, video.attached_picture))
+            .collect::<Vec<_>>();
+        let index = selector.find(&streams).ok()?;
+
+        self.video_streams.iter().find(|video| video.index == index)
+    }
+
+    /// Returns the audio stream matching the selector
+    pub fn find_audio_stream(&self, selector: StreamSelector) -> Option<&AudioStreamInfo> {
+        let streams = self
+            .audio_streams
+            .iter()
+            .map(|audio| (audio.index, false))
+            .collect::<Vec<_>>();
+        let index = selector.find(&streams).ok()?;
+
+        self.audio_streams.iter().find(|audio| audio.index == index)
+    }
+
+    /// Returns the creation time of the file (ISO 8601), as stored by the
+    /// muxer in the container tags
+    pub fn creation_time(&self) -> Option<&str> {
+        self.format.creation_time.as_deref()
+    }
+}
+
+/// Informations about the container format
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct FormatInfo {
+    /// Short name(s) of the format, ie `mov,mp4,m4a,3gp,3g2,mj2`
+    pub format_name: String,
+    /// Human readable name of the format
+    pub format_long_name: String,
+    /// Total number of streams, including the ones not described here
+    pub nb_streams: u32,
+    /// Duration of the whole file
+    #[serde(serialize_with = "seconds")]
+    pub duration: Option<Duration>,
+    /// Size of the file in bytes
+    pub size: Option<u64>,
+    /// Overall bitrate in bits per second
+    pub bit_rate: Option<u64>,
+    /// Creation time (ISO 8601) from the `creation_time` tag
+    pub creation_time: Option<String>,
+    /// All the container tags
+    pub tags: HashMap<String, String>,
+}
+
+/// Informations about a video stream
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct VideoStreamInfo {
+    /// Index of the stream in the container
+    pub index: u32,
+    pub codec_name: String,
+    pub codec_long_name: String,
+    pub profile: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    pub pixel_format: PixelFormat,
+    /// The stream has an alpha channel, either in its pixel format or stored
+    /// apart (VP8/VP9 in WebM)
+    pub has_alpha: bool,
+    /// Real base frame rate of the stream (`r_frame_rate`)
+    pub frame_rate: Option<Rational>,
+    /// Average frame rate of the stream (`avg_frame_rate`)
+    pub avg_frame_rate: Option<Rational>,
+    /// The stream has a variable frame rate, its frames are not evenly spaced
+    pub variable_frame_rate: bool,
+    /// Unit in which the timestamps of the stream are expressed
+    pub time_base: Option<Rational>,
+    #[serde(serialize_with = "seconds")]
+    pub duration: Option<Duration>,
+    pub nb_frames: Option<u64>,
+    /// Bitrate in bits per second
+    pub bit_rate: Option<u64>,
+    /// Aspect ratio of a single pixel
+    pub sample_aspect_ratio: Option<Rational>,
+    /// Aspect ratio of the displayed picture
+    pub display_aspect_ratio: Option<Rational>,
+    /// Clockwise rotation in degrees, in `[0, 360)`, to apply when
+    /// displaying the video
+    pub rotation: i32,
+    /// The stream is a cover art, a single picture attached to the file
+    pub attached_picture: bool,
+    /// Timecode of the first frame, from the `timecode` tag
+    pub timecode: Option<Timecode>,
+    pub color_space: Option<String>,
+    pub color_range: Option<String>,
+    pub color_primaries: Option<String>,
+    pub color_transfer: Option<String>,
+    pub tags: HashMap<String, String>,
+}
+
+/// Informations about an audio stream
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct AudioStreamInfo {
+    /// Index of the stream in the container
+    pub index: u32,
+    pub codec_name: String,
+    pub codec_long_name: String,
+    pub profile: Option<String>,
+    /// FFMpeg name of the sample format, ie `fltp`
+    pub sample_fmt: String,
+    /// Number of samples per second
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub channel_layout: Option<String>,
+    /// Unit in which the timestamps of the stream are expressed
+    pub time_base: Option<Rational>,
+    #[serde(serialize_with = "seconds")]
+    pub duration: Option<Duration>,
+    pub nb_frames: Option<u64>,
+    /// Bitrate in bits per second
+    pub bit_rate: Option<u64>,
+    pub tags: HashMap<String, String>,
+}
+
+/// Serializes durations as seconds, as ffprobe does
+fn seconds<S: serde::Serializer>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_some(&duration.map(|duration| duration.as_secs_f64()))
+}
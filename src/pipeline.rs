@@ -0,0 +1,527 @@
+use crate::{
+    effects::FrameContext, Clip, Crop, Easing, Effect, EffectsExt, EncoderSettings,
+    FFMpegVideoWriter, Frame, FrameInfo, FrameSink, Grayscale, ImageSequenceWriter, KenBurns,
+    Rational, Rect, Render, Resize, Y4mWriter,
+};
+use image::{Luma, Rgb};
+use std::path::{Path, PathBuf};
+
+/// An effect of a pipeline, as written in a config file, ie
+/// `{"crop": {"x": 0, "y": 0, "width": 640, "height": 360}}`
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum EffectConfig {
+    Crop {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    Resize {
+        width: u32,
+        height: u32,
+    },
+    /// Frames stay RGB, with equal channels
+    Grayscale {},
+    KenBurns {
+        start: Rect,
+        end: Rect,
+        #[serde(default)]
+        easing: Easing,
+        width: u32,
+        height: u32,
+        /// Number of frames to reach `end`, all the frames of the clip by
+        /// default
+        #[serde(default)]
+        frames: Option<u64>,
+    },
+}
+
+/// Where the frames of a pipeline are written.
+/// The writer depends on the path: a pattern with `%d` writes an image
+/// sequence starting at 1, a `.y4m` file a raw YUV4MPEG2 stream, and any
+/// other file is encoded by FFMpeg.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputConfig {
+    pub path: PathBuf,
+    /// Frame rate of the output, the one of the clip by default. Frames are
+    /// dropped or repeated to reach it.
+    #[serde(default)]
+    pub frame_rate: Option<Rational>,
+    /// Encoder of the videos written by FFMpeg, unused for `.y4m` files and
+    /// image sequences
+    #[serde(default)]
+    pub encoder: EncoderSettings,
+}
+
+/// A chain of effects applied to the frames of a clip, and where to write
+/// them, as described in a JSON or TOML config file.
+///
+/// ```json
+/// {
+///     "effects": [
+///         {"crop": {"x": 0, "y": 0, "width": 1080, "height": 1080}},
+///         {"resize": {"width": 540, "height": 540}},
+///         {"grayscale": {}}
+///     ],
+///     "output": {"path": "out.mp4"}
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PipelineConfig {
+    #[serde(default)]
+    pub effects: Vec<EffectConfig>,
+    pub output: OutputConfig,
+}
+
+impl PipelineConfig {
+    /// Parses a JSON config
+    pub fn from_json(json: &str) -> eyre::Result<Self> {
+        serde_json::from_str(json).map_err(|err| eyre::eyre!("invalid pipeline config: {}", err))
+    }
+
+    /// Parses a TOML config
+    pub fn from_toml(toml: &str) -> eyre::Result<Self> {
+        toml::from_str(toml).map_err(|err| eyre::eyre!("invalid pipeline config: {}", err))
+    }
+
+    /// Reads a config file, in TOML if its extension is `.toml`, in JSON
+    /// otherwise
+    pub fn from_file(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| eyre::eyre!("cannot read {:?}: {}", path, err))?;
+
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Self::from_toml(&content),
+            _ => Self::from_json(&content),
+        }
+    }
+
+    /// Checks the parameters of the effects against the dimensions of the
+    /// frames they get, and returns the dimensions of the output frames
+    pub fn validate(&self, dimensions: (u32, u32)) -> eyre::Result<(u32, u32)> {
+        self.effects
+            .iter()
+            .enumerate()
+            .try_fold(dimensions, |dimensions, (index, effect)| {
+                effect
+                    .validate(dimensions)
+                    .map_err(|err| eyre::eyre!("effect {} ({:?}): {}", index, effect, err))
+            })
+    }
+
+    /// Returns the chain of effects, for a stream of `nb_frames` frames if
+    /// known
+    pub fn effects(&self, nb_frames: Option<u64>) -> eyre::Result<Vec<Box<dyn Effect<Rgb<u8>>>>> {
+        self.effects
+            .iter()
+            .map(|effect| effect.build(nb_frames))
+            .collect()
+    }
+
+    /// Applies the effects to the frames of the clip, and writes them.
+    /// The config is validated before any frame is read.
+    pub fn run(&self, clip: &Clip<Rgb<u8>>) -> eyre::Result<()> {
+        self.render(clip, Render::new())
+    }
+
+    /// Runs the pipeline with `render`, to follow its progress or cancel it
+    pub fn render(&self, clip: &Clip<Rgb<u8>>, render: Render<'_>) -> eyre::Result<()> {
+        let dimensions = self.validate(clip.dimensions())?;
+        let frame_rate = self.output.frame_rate.unwrap_or(clip.frame_rate());
+        if frame_rate.num() <= 0 {
+            eyre::bail!("invalid output frame rate {}", frame_rate);
+        }
+
+        let frames = clip.clone().iter_frames()?;
+        let effects = self.effects(frames.size_hint().1.map(|upper| upper as u64))?;
+
+        let render = render.read_error(frames.read_error());
+        let mut sink = self.output.sink(dimensions, frame_rate)?;
+        let frames = frames.apply(effects);
+        if frame_rate == clip.frame_rate() {
+            render.run(frames, sink.as_mut())
+        } else {
+            render.run(
+                Retime::new(frames, clip.frame_rate(), frame_rate),
+                sink.as_mut(),
+            )
+        }
+    }
+}
+
+impl EffectConfig {
+    /// Returns the dimensions of the frames output by the effect
+    fn validate(&self, (frame_width, frame_height): (u32, u32)) -> eyre::Result<(u32, u32)> {
+        match *self {
+            Self::Crop {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                if width == 0 || height == 0 {
+                    eyre::bail!("empty crop");
+                }
+                if x as u64 + width as u64 > frame_width as u64
+                    || y as u64 + height as u64 > frame_height as u64
+                {
+                    eyre::bail!("crop is out of the {}x{} frame", frame_width, frame_height);
+                }
+                Ok((width, height))
+            }
+            Self::Resize { width, height } if width == 0 || height == 0 => {
+                eyre::bail!("cannot resize to {}x{}", width, height)
+            }
+            Self::Resize { width, height } => Ok((width, height)),
+            Self::Grayscale {} => Ok((frame_width, frame_height)),
+            Self::KenBurns {
+                start,
+                end,
+                width,
+                height,
+                ..
+            } => {
+                if width == 0 || height == 0 {
+                    eyre::bail!("cannot resample to {}x{}", width, height);
+                }
+                for rect in [start, end] {
+                    let inside = rect.x >= 0.0
+                        && rect.y >= 0.0
+                        && rect.width > 0.0
+                        && rect.height > 0.0
+                        && rect.x + rect.width <= frame_width as f32
+                        && rect.y + rect.height <= frame_height as f32;
+                    if !inside {
+                        eyre::bail!(
+                            "rectangle {:?} is out of the {}x{} frame",
+                            rect,
+                            frame_width,
+                            frame_height
+                        );
+                    }
+                }
+                Ok((width, height))
+            }
+        }
+    }
+
+    fn build(&self, nb_frames: Option<u64>) -> eyre::Result<Box<dyn Effect<Rgb<u8>>>> {
+        let effect: Box<dyn Effect<Rgb<u8>>> = match *self {
+            Self::Crop {
+                x,
+                y,
+                width,
+                height,
+            } => Box::new(Crop::new(x, y, width, height)),
+            Self::Resize { width, height } => Box::new(Resize::new(width, height)),
+            Self::Grayscale {} => Box::new(RgbGrayscale),
+            Self::KenBurns {
+                start,
+                end,
+                easing,
+                width,
+                height,
+                frames,
+            } => {
+                let Some(frames) = frames.or(nb_frames) else {
+                    eyre::bail!("unknown number of frames for ken_burns, set its `frames`");
+                };
+                Box::new(KenBurns::new(start, end, easing, width, height, frames))
+            }
+        };
+
+        Ok(effect)
+    }
+}
+
+impl OutputConfig {
+    /// Returns the writer of the output, for frames of the given dimensions
+    pub fn sink(
+        &self,
+        dimensions: (u32, u32),
+        frame_rate: Rational,
+    ) -> eyre::Result<Box<dyn FrameSink<Rgb<u8>>>> {
+        let path = &self.path;
+        let is_pattern = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.contains('%'));
+
+        Ok(if is_pattern {
+            Box::new(ImageSequenceWriter::to_pattern(path, 1)?)
+        } else if path.extension().is_some_and(|extension| extension == "y4m") {
+            Box::new(Y4mWriter::to_file(path, dimensions, frame_rate)?)
+        } else {
+            Box::new(FFMpegVideoWriter::to_file_with_encoder(
+                path,
+                dimensions,
+                frame_rate,
+                "rgb24",
+                &self.encoder,
+            )?)
+        })
+    }
+}
+
+/// Grayscale keeping RGB frames, so that it can be chained with the other
+/// effects
+struct RgbGrayscale;
+
+impl Effect<Rgb<u8>> for RgbGrayscale {
+    fn apply(&mut self, frame: Frame<Rgb<u8>>, context: &FrameContext) -> Frame<Rgb<u8>> {
+        Effect::<_, Luma<u8>>::apply(&mut Grayscale, frame, context)
+            .transform(|Luma([value])| Rgb([*value; 3]))
+    }
+}
+
+/// Drops or repeats frames to change their rate: each output frame is the
+/// source frame shown at its time
+struct Retime<I> {
+    frames: I,
+    from: Rational,
+    to: Rational,
+    index: u64, // next output frame
+    read: u64,  // source frames read
+    current: Option<Frame<Rgb<u8>>>,
+}
+
+impl<I> Retime<I>
+where
+    I: Iterator<Item = Frame<Rgb<u8>>>,
+{
+    fn new(frames: I, from: Rational, to: Rational) -> Self {
+        Self {
+            frames,
+            from,
+            to,
+            index: 0,
+            read: 0,
+            current: None,
+        }
+    }
+
+    /// Returns the source frame shown at the time of the output frame
+    /// `index`
+    fn source(&self, index: u64) -> u64 {
+        let num = index as i128 * self.from.num() as i128 * self.to.den() as i128;
+        (num / (self.from.den() as i128 * self.to.num() as i128)) as u64
+    }
+
+    /// Returns the number of output frames of `count` source frames
+    fn output_frames(&self, count: usize) -> usize {
+        let num = count as i128 * self.to.num() as i128 * self.from.den() as i128;
+        let den = self.to.den() as i128 * self.from.num() as i128;
+        let total = ((num + den - 1) / den) as usize;
+
+        total.saturating_sub(self.index as usize)
+    }
+}
+
+impl<I> Iterator for Retime<I>
+where
+    I: Iterator<Item = Frame<Rgb<u8>>>,
+{
+    type Item = Frame<Rgb<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let source = self.source(self.index);
+        while self.read <= source {
+            self.current = Some(self.frames.next()?);
+            self.read += 1;
+        }
+
+        let info = FrameInfo {
+            index: self.index,
+            pts: self.index as i64,
+            duration: 1,
+            time_base: self.to.recip(),
+            ..*self.current.as_ref()?.info()
+        };
+        self.index += 1;
+
+        // The frame is copied only if it is repeated
+        let frame = if self.source(self.index) == source {
+            let current = self.current.as_ref()?;
+            match current.pool() {
+                Some(pool) => {
+                    let mut copy = pool.frame(current.dimensions());
+                    copy.image_mut().copy_from_slice(current.as_raw());
+                    copy
+                }
+                None => Frame::from_vec(current.as_raw().to_vec(), current.dimensions()),
+            }
+        } else {
+            self.current.take()?
+        };
+
+        Some(frame.with_info(info))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower, upper) = self.frames.size_hint();
+        let read = self.read as usize;
+
+        (
+            self.output_frames(read + lower),
+            upper.map(|upper| self.output_frames(read + upper)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JSON: &str = r#"{
+        "effects": [
+            {"crop": {"x": 10, "y": 0, "width": 100, "height": 50}},
+            {"resize": {"width": 50, "height": 25}},
+            {"grayscale": {}},
+            {"ken_burns": {
+                "start": {"x": 0, "y": 0, "width": 50, "height": 25},
+                "end": {"x": 10, "y": 5, "width": 20, "height": 10},
+                "easing": "ease_in_out",
+                "width": 16,
+                "height": 8
+            }}
+        ],
+        "output": {"path": "out.y4m", "frame_rate": "30000/1001"}
+    }"#;
+
+    #[test]
+    fn parse() {
+        let config = PipelineConfig::from_json(JSON).unwrap();
+        assert_eq!(config.effects.len(), 4);
+        assert_eq!(config.effects[2], EffectConfig::Grayscale {});
+        assert_eq!(config.output.frame_rate, Some(Rational::new(30000, 1001)));
+        assert_eq!(config.output.encoder, EncoderSettings::default());
+
+        let toml = PipelineConfig::from_toml(
+            r#"
+            [[effects]]
+            crop = { x = 10, y = 0, width = 100, height = 50 }
+
+            [[effects]]
+            resize = { width = 50, height = 25 }
+
+            [[effects]]
+            grayscale = {}
+
+            [[effects]]
+            ken_burns = { start = { x = 0, y = 0, width = 50, height = 25 }, end = { x = 10, y = 5, width = 20, height = 10 }, easing = "ease_in_out", width = 16, height = 8 }
+
+            [output]
+            path = "out.y4m"
+            frame_rate = "30000/1001"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(toml, config);
+
+        let config = PipelineConfig::from_toml(
+            r#"
+            [output]
+            path = "out.mp4"
+            encoder = { codec = "libx265", preset = "slow", crf = 24 }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.output.encoder.codec, "libx265");
+        assert_eq!(config.output.encoder.preset.as_deref(), Some("slow"));
+        assert_eq!(config.output.encoder.crf, Some(24));
+        assert_eq!(config.output.encoder.pix_fmt, None);
+
+        assert!(PipelineConfig::from_json(
+            r#"{"effects": [{"blur": {}}], "output": {"path": "a.mp4"}}"#
+        )
+        .is_err());
+        assert!(PipelineConfig::from_json(
+            r#"{"effects": [{"resize": {"width": 1}}], "output": {"path": "a.mp4"}}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn validate() {
+        let config = PipelineConfig::from_json(JSON).unwrap();
+        assert_eq!(config.validate((200, 100)).unwrap(), (16, 8));
+        // The crop does not fit
+        assert!(config.validate((100, 100)).is_err());
+
+        let mut config = config;
+        config.effects.truncate(2);
+        config.effects.push(EffectConfig::KenBurns {
+            start: Rect::new(0.0, 0.0, 50.0, 25.0),
+            end: Rect::new(40.0, 0.0, 20.0, 10.0),
+            easing: Easing::Linear,
+            width: 16,
+            height: 8,
+            frames: None,
+        });
+        assert!(config.validate((200, 100)).is_err());
+    }
+
+    #[test]
+    fn effects() {
+        let config = PipelineConfig::from_json(JSON).unwrap();
+        let frames = crate::Generator::<Rgb<u8>>::new(
+            crate::Pattern::Solid(crate::Color::rgb(255, 0, 0)),
+            (200, 100),
+            Rational::new(25, 1),
+            3,
+        )
+        .apply(config.effects(Some(3)).unwrap())
+        .collect::<Vec<_>>();
+
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].dimensions(), (16, 8));
+        assert!(frames[0].as_raw().iter().all(|value| *value == 76));
+
+        // The ken burns effect needs the number of frames
+        assert!(config.effects(None).is_err());
+        let mut config = config;
+        if let EffectConfig::KenBurns { frames, .. } = &mut config.effects[3] {
+            *frames = Some(3);
+        }
+        assert!(config.effects(None).is_ok());
+    }
+
+    #[test]
+    fn retime() {
+        let rate = Rational::new(25, 1);
+        let frames = || {
+            (0..5).map(|index| {
+                Frame::<Rgb<u8>>::from_vec(vec![index; 3], (1, 1)).with_info(FrameInfo {
+                    index: index as u64,
+                    ..FrameInfo::default()
+                })
+            })
+        };
+
+        // Doubles the frame rate, repeating each frame
+        let retimed = Retime::new(frames(), rate, Rational::new(50, 1));
+        assert_eq!(retimed.size_hint(), (10, Some(10)));
+        let frames_50 = retimed.collect::<Vec<_>>();
+        let values = frames_50.iter().map(|frame| frame.as_raw()[0]);
+        assert_eq!(values.collect::<Vec<_>>(), [0, 0, 1, 1, 2, 2, 3, 3, 4, 4]);
+        assert_eq!(frames_50[3].info().index, 3);
+        assert_eq!(frames_50[3].info().time_base, Rational::new(1, 50));
+
+        // Drops frames
+        let mut retimed = Retime::new(frames(), rate, Rational::new(10, 1));
+        assert_eq!(retimed.size_hint(), (2, Some(2)));
+        retimed.next();
+        assert_eq!(retimed.size_hint(), (1, Some(1)));
+        let values = retimed.map(|frame| frame.as_raw()[0]);
+        assert_eq!(values.collect::<Vec<_>>(), [2]);
+
+        let retimed = Retime::new(frames(), rate, Rational::new(30000, 1001));
+        assert_eq!(retimed.size_hint(), (6, Some(6)));
+        let values = retimed.map(|frame| frame.as_raw()[0]);
+        assert_eq!(values.collect::<Vec<_>>(), [0, 0, 1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,369 @@
+use image::{Luma, LumaA, Pixel, Rgb, Rgba};
+use std::{fmt::Display, str::FromStr};
+
+/// Pixel format of a video stream, as named by FFMpeg.
+/// Only the most common ones have their own variant, the others are kept as
+/// `Other` with their FFMpeg name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PixelFormat {
+    // Planar YUV
+    Yuv420p,
+    Yuvj420p,
+    Yuv422p,
+    Yuvj422p,
+    Yuv444p,
+    Yuvj444p,
+    Yuv420p10le,
+    Yuv422p10le,
+    Yuv444p10le,
+    Yuv420p12le,
+    Yuv422p12le,
+    Yuv444p12le,
+    // Planar YUV with alpha
+    Yuva420p,
+    Yuva444p,
+    Yuva444p10le,
+    Yuva444p12le,
+    // Semi-planar YUV
+    Nv12,
+    Nv21,
+    P010le,
+    // Packed YUV
+    Yuyv422,
+    Uyvy422,
+    // Gray
+    Gray,
+    Gray10le,
+    Gray12le,
+    Gray16le,
+    Ya8,
+    // RGB
+    Rgb24,
+    Bgr24,
+    Rgba,
+    Bgra,
+    Argb,
+    Abgr,
+    Rgb48le,
+    Rgba64le,
+    Gbrp,
+    Gbrp10le,
+    Gbrp12le,
+    Gbrap,
+    Pal8,
+    Other(String),
+}
+
+impl PixelFormat {
+    /// Returns the FFMpeg name of the pixel format
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Yuv420p => "yuv420p",
+            Self::Yuvj420p => "yuvj420p",
+            Self::Yuv422p => "yuv422p",
+            Self::Yuvj422p => "yuvj422p",
+            Self::Yuv444p => "yuv444p",
+            Self::Yuvj444p => "yuvj444p",
+            Self::Yuv420p10le => "yuv420p10le",
+            Self::Yuv422p10le => "yuv422p10le",
+            Self::Yuv444p10le => "yuv444p10le",
+            Self::Yuv420p12le => "yuv420p12le",
+            Self::Yuv422p12le => "yuv422p12le",
+            Self::Yuv444p12le => "yuv444p12le",
+            Self::Yuva420p => "yuva420p",
+            Self::Yuva444p => "yuva444p",
+            Self::Yuva444p10le => "yuva444p10le",
+            Self::Yuva444p12le => "yuva444p12le",
+            Self::Nv12 => "nv12",
+            Self::Nv21 => "nv21",
+            Self::P010le => "p010le",
+            Self::Yuyv422 => "yuyv422",
+            Self::Uyvy422 => "uyvy422",
+            Self::Gray => "gray",
+            Self::Gray10le => "gray10le",
+            Self::Gray12le => "gray12le",
+            Self::Gray16le => "gray16le",
+            Self::Ya8 => "ya8",
+            Self::Rgb24 => "rgb24",
+            Self::Bgr24 => "bgr24",
+            Self::Rgba => "rgba",
+            Self::Bgra => "bgra",
+            Self::Argb => "argb",
+            Self::Abgr => "abgr",
+            Self::Rgb48le => "rgb48le",
+            Self::Rgba64le => "rgba64le",
+            Self::Gbrp => "gbrp",
+            Self::Gbrp10le => "gbrp10le",
+            Self::Gbrp12le => "gbrp12le",
+            Self::Gbrap => "gbrap",
+            Self::Pal8 => "pal8",
+            Self::Other(name) => name,
+        }
+    }
+
+    /// Returns the pixel format from its FFMpeg name
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "yuv420p" => Self::Yuv420p,
+            "yuvj420p" => Self::Yuvj420p,
+            "yuv422p" => Self::Yuv422p,
+            "yuvj422p" => Self::Yuvj422p,
+            "yuv444p" => Self::Yuv444p,
+            "yuvj444p" => Self::Yuvj444p,
+            "yuv420p10le" => Self::Yuv420p10le,
+            "yuv422p10le" => Self::Yuv422p10le,
+            "yuv444p10le" => Self::Yuv444p10le,
+            "yuv420p12le" => Self::Yuv420p12le,
+            "yuv422p12le" => Self::Yuv422p12le,
+            "yuv444p12le" => Self::Yuv444p12le,
+            "yuva420p" => Self::Yuva420p,
+            "yuva444p" => Self::Yuva444p,
+            "yuva444p10le" => Self::Yuva444p10le,
+            "yuva444p12le" => Self::Yuva444p12le,
+            "nv12" => Self::Nv12,
+            "nv21" => Self::Nv21,
+            "p010le" => Self::P010le,
+            "yuyv422" => Self::Yuyv422,
+            "uyvy422" => Self::Uyvy422,
+            "gray" => Self::Gray,
+            "gray10le" => Self::Gray10le,
+            "gray12le" => Self::Gray12le,
+            "gray16le" => Self::Gray16le,
+            "ya8" => Self::Ya8,
+            "rgb24" => Self::Rgb24,
+            "bgr24" => Self::Bgr24,
+            "rgba" => Self::Rgba,
+            "bgra" => Self::Bgra,
+            "argb" => Self::Argb,
+            "abgr" => Self::Abgr,
+            "rgb48le" => Self::Rgb48le,
+            "rgba64le" => Self::Rgba64le,
+            "gbrp" => Self::Gbrp,
+            "gbrp10le" => Self::Gbrp10le,
+            "gbrp12le" => Self::Gbrp12le,
+            "gbrap" => Self::Gbrap,
+            "pal8" => Self::Pal8,
+            other => Self::Other(other.to_string()),
+        }
+    }
+
+    /// Returns the number of bits per component.
+    /// For unknown formats, it is guessed from the name (`yuv420p9le`).
+    pub fn bit_depth(&self) -> u8 {
+        match self {
+            Self::Yuv420p10le
+            | Self::Yuv422p10le
+            | Self::Yuv444p10le
+            | Self::Yuva444p10le
+            | Self::P010le
+            | Self::Gray10le
+            | Self::Gbrp10le => 10,
+            Self::Yuv420p12le
+            | Self::Yuv422p12le
+            | Self::Yuv444p12le
+            | Self::Yuva444p12le
+            | Self::Gray12le
+            | Self::Gbrp12le => 12,
+            Self::Gray16le | Self::Rgb48le | Self::Rgba64le => 16,
+            Self::Other(name) => ["16", "14", "12", "10", "9"]
+                .into_iter()
+                .find(|depth| {
+                    name.strip_suffix("le")
+                        .or_else(|| name.strip_suffix("be"))
+                        .is_some_and(|name| name.ends_with(depth))
+                })
+                .and_then(|depth| depth.parse().ok())
+                .unwrap_or(8),
+            _ => 8,
+        }
+    }
+
+    /// Returns true if the pixel format has more than 8 bits per component,
+    /// and should be read as 16 bits frames to avoid losing precision.
+    pub fn is_high_bit_depth(&self) -> bool {
+        self.bit_depth() > 8
+    }
+
+    /// Returns true if the pixel format has an alpha channel.
+    /// For unknown formats, it is guessed from the name.
+    pub fn has_alpha(&self) -> bool {
+        match self {
+            Self::Yuva420p
+            | Self::Yuva444p
+            | Self::Yuva444p10le
+            | Self::Yuva444p12le
+            | Self::Ya8
+            | Self::Rgba
+            | Self::Bgra
+            | Self::Argb
+            | Self::Abgr
+            | Self::Rgba64le
+            | Self::Gbrap => true,
+            Self::Other(name) => ["yuva", "gbrap", "rgba", "bgra", "argb", "abgr", "ya"]
+                .iter()
+                .any(|prefix| name.starts_with(prefix)),
+            _ => false,
+        }
+    }
+
+    /// Returns true if the pixel format only has a luminance component
+    pub fn is_gray(&self) -> bool {
+        match self {
+            Self::Gray | Self::Gray10le | Self::Gray12le | Self::Gray16le | Self::Ya8 => true,
+            Self::Other(name) => name.starts_with("gray") || name.starts_with("ya"),
+            _ => false,
+        }
+    }
+}
+
+/// Serialized as its string representation
+impl serde::Serialize for PixelFormat {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl Display for PixelFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for PixelFormat {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::from_name(s))
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Pixel types that frames can be read as (and written from).
+/// FFMpeg converts the source pixel format to the raw one given here.
+/// 16 bits formats use the native endianness.
+pub trait RawPixel: Pixel {
+    /// FFMpeg name of the raw pixel format
+    const PIX_FMT: &'static str;
+
+    /// Converts the raw bytes output by FFMpeg to subpixels
+    fn from_raw_bytes(bytes: Vec<u8>) -> Vec<Self::Subpixel>;
+
+    /// Converts subpixels to the raw bytes expected by FFMpeg
+    fn to_raw_bytes(subpixels: &[Self::Subpixel]) -> Vec<u8>;
+
+    /// Views subpixels as the raw bytes expected by FFMpeg, without copying
+    fn as_raw_bytes(subpixels: &[Self::Subpixel]) -> &[u8];
+
+    /// Views subpixels as raw bytes, for FFMpeg to write into
+    fn as_raw_bytes_mut(subpixels: &mut [Self::Subpixel]) -> &mut [u8];
+
+    /// Number of bytes taken by a single pixel
+    fn bytes_per_pixel() -> usize {
+        Self::CHANNEL_COUNT as usize * std::mem::size_of::<Self::Subpixel>()
+    }
+}
+
+macro_rules! raw_pixel_u8 {
+    ($pixel:ty, $pix_fmt:literal) => {
+        impl RawPixel for $pixel {
+            const PIX_FMT: &'static str = $pix_fmt;
+
+            fn from_raw_bytes(bytes: Vec<u8>) -> Vec<u8> {
+                bytes
+            }
+
+            fn to_raw_bytes(subpixels: &[u8]) -> Vec<u8> {
+                subpixels.to_vec()
+            }
+
+            fn as_raw_bytes(subpixels: &[u8]) -> &[u8] {
+                subpixels
+            }
+
+            fn as_raw_bytes_mut(subpixels: &mut [u8]) -> &mut [u8] {
+                subpixels
+            }
+        }
+    };
+}
+
+macro_rules! raw_pixel_u16 {
+    ($pixel:ty, $pix_fmt:literal) => {
+        impl RawPixel for $pixel {
+            const PIX_FMT: &'static str = if cfg!(target_endian = "little") {
+                concat!($pix_fmt, "le")
+            } else {
+                concat!($pix_fmt, "be")
+            };
+
+            fn from_raw_bytes(bytes: Vec<u8>) -> Vec<u16> {
+                bytes
+                    .chunks_exact(2)
+                    .map(|bytes| u16::from_ne_bytes([bytes[0], bytes[1]]))
+                    .collect()
+            }
+
+            fn to_raw_bytes(subpixels: &[u16]) -> Vec<u8> {
+                subpixels
+                    .iter()
+                    .flat_map(|subpixel| subpixel.to_ne_bytes())
+                    .collect()
+            }
+
+            // The pixel formats are native endian, as the subpixels
+            fn as_raw_bytes(subpixels: &[u16]) -> &[u8] {
+                bytemuck::cast_slice(subpixels)
+            }
+
+            fn as_raw_bytes_mut(subpixels: &mut [u16]) -> &mut [u8] {
+                bytemuck::cast_slice_mut(subpixels)
+            }
+        }
+    };
+}
+
+raw_pixel_u8!(Rgb<u8>, "rgb24");
+raw_pixel_u8!(Rgba<u8>, "rgba");
+raw_pixel_u8!(Luma<u8>, "gray");
+raw_pixel_u8!(LumaA<u8>, "ya8");
+raw_pixel_u16!(Rgb<u16>, "rgb48");
+raw_pixel_u16!(Rgba<u16>, "rgba64");
+raw_pixel_u16!(Luma<u16>, "gray16");
+raw_pixel_u16!(LumaA<u16>, "ya16");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixel_format() {
+        let format: PixelFormat = "yuva444p12le".parse().unwrap();
+        assert_eq!(format, PixelFormat::Yuva444p12le);
+        assert_eq!(format.bit_depth(), 12);
+        assert!(format.has_alpha());
+        assert_eq!(format.to_string(), "yuva444p12le");
+
+        let format: PixelFormat = "nv12".parse().unwrap();
+        assert_eq!(format.bit_depth(), 8);
+        assert!(!format.has_alpha());
+        assert!(!format.is_gray());
+
+        let format: PixelFormat = "yuva422p9be".parse().unwrap();
+        assert_eq!(format, PixelFormat::Other("yuva422p9be".into()));
+        assert_eq!(format.bit_depth(), 9);
+        assert!(format.has_alpha());
+        assert!(format.is_high_bit_depth());
+    }
+
+    #[test]
+    fn raw_bytes() {
+        let subpixels = vec![0x0102u16, 0xfffe, 0x0000];
+        let bytes = Rgb::<u16>::to_raw_bytes(&subpixels);
+        assert_eq!(bytes.len(), 6);
+        assert_eq!(Rgb::<u16>::as_raw_bytes(&subpixels), bytes);
+        assert_eq!(Rgb::<u16>::from_raw_bytes(bytes), subpixels);
+        assert_eq!(Rgba::<u16>::bytes_per_pixel(), 8);
+        assert_eq!(Luma::<u8>::bytes_per_pixel(), 1);
+    }
+}
@@ -0,0 +1,102 @@
+use crate::Frame;
+use image::{Pixel, Primitive};
+use std::sync::{Arc, Mutex};
+
+/// A pool of frame buffers.
+/// Frames taken from a pool give their buffer back when dropped, so that the
+/// next frames reuse it instead of allocating. Clones share the same buffers,
+/// which makes it possible to share a pool between pipelines.
+#[derive(Clone)]
+pub struct FramePool<S> {
+    buffers: Arc<Mutex<Vec<Vec<S>>>>,
+    capacity: usize, // maximum number of buffers kept
+}
+
+impl<S: Primitive> FramePool<S> {
+    /// Creates a pool keeping at most `capacity` unused buffers, extra ones
+    /// being freed
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            buffers: Arc::new(Mutex::new(Vec::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    /// Returns the number of unused buffers
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns true if there is no unused buffer
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a frame of the given (width, height), whose buffer goes back
+    /// to the pool when dropped.
+    /// The content of the frame is unspecified, as the buffer may have been
+    /// used by a previous frame.
+    pub fn frame<P>(&self, (width, height): (u32, u32)) -> Frame<P>
+    where
+        P: Pixel<Subpixel = S>,
+    {
+        let len = width as usize * height as usize * P::CHANNEL_COUNT as usize;
+
+        Frame::from_vec(self.take(len), (width, height)).with_pool(self.clone())
+    }
+
+    /// Returns a buffer of `len` subpixels, with an unspecified content
+    pub(crate) fn take(&self, len: usize) -> Vec<S> {
+        let mut buffers = self.lock();
+        let mut buffer = match buffers.iter().position(|buffer| buffer.capacity() >= len) {
+            Some(index) => buffers.swap_remove(index),
+            None => buffers.pop().unwrap_or_default(),
+        };
+        drop(buffers);
+
+        buffer.resize(len, S::DEFAULT_MIN_VALUE);
+        buffer
+    }
+
+    /// Gives a buffer back to the pool
+    pub(crate) fn recycle(&self, buffer: Vec<S>) {
+        let mut buffers = self.lock();
+        if buffer.capacity() > 0 && buffers.len() < self.capacity {
+            buffers.push(buffer);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Vec<S>>> {
+        // Buffers are only pushed and popped, they are fine after a panic
+        self.buffers
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Luma, Rgb};
+
+    #[test]
+    fn recycle() {
+        let pool = FramePool::<u8>::new(1);
+        let frame = pool.frame::<Rgb<u8>>((4, 4));
+        let pointer = frame.as_raw().as_ptr();
+        assert!(pool.is_empty());
+
+        drop(frame);
+        assert_eq!(pool.len(), 1);
+        // Smaller frames reuse the buffer
+        let frame = pool.frame::<Luma<u8>>((4, 2));
+        assert_eq!(frame.as_raw().as_ptr(), pointer);
+        assert_eq!(frame.as_raw().len(), 8);
+
+        // The pool keeps a single buffer
+        let other = pool.frame::<Luma<u8>>((4, 2));
+        drop(frame);
+        drop(other);
+        assert_eq!(pool.len(), 1);
+    }
+}
@@ -0,0 +1,101 @@
+use crate::{Rational, Timecode};
+use std::time::Duration;
+
+/// A position in a clip, either as a frame index, a time or a timecode.
+/// A time designates the frame being displayed at that time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Position {
+    Frame(u64),
+    Time(Duration),
+    Timecode(Timecode),
+}
+
+impl Position {
+    /// Returns the index of the frame at this position.
+    /// A timecode in another frame rate is converted using its time.
+    pub fn to_frame(&self, frame_rate: Rational) -> u64 {
+        match self {
+            Self::Frame(frame) => *frame,
+            Self::Time(time) => frame_rate.frames_in(*time),
+            Self::Timecode(timecode) if timecode.frame_rate() == frame_rate => timecode.frame(),
+            Self::Timecode(timecode) => frame_rate.frames_in(timecode.to_duration()),
+        }
+    }
+
+    /// Returns the time at this position
+    pub fn to_time(&self, frame_rate: Rational) -> Duration {
+        match self {
+            Self::Frame(frame) => frame_rate.frame_time(*frame),
+            Self::Time(time) => *time,
+            Self::Timecode(timecode) => timecode.to_duration(),
+        }
+    }
+}
+
+impl From<u64> for Position {
+    fn from(frame: u64) -> Self {
+        Self::Frame(frame)
+    }
+}
+
+impl From<Duration> for Position {
+    fn from(time: Duration) -> Self {
+        Self::Time(time)
+    }
+}
+
+impl From<Timecode> for Position {
+    fn from(timecode: Timecode) -> Self {
+        Self::Timecode(timecode)
+    }
+}
+
+/// Returns the range of frames `[start, end)` starting at `start` and lasting
+/// `during`.
+/// When `during` is a time, the end is computed from the start time rather
+/// than the start frame, so that adjacent ranges expressed in time never
+/// overlap nor leave a gap.
+pub(crate) fn frame_range(start: Position, during: Position, frame_rate: Rational) -> (u64, u64) {
+    let start_frame = start.to_frame(frame_rate);
+    let end_frame = match during {
+        Position::Time(during) => {
+            Position::Time(start.to_time(frame_rate) + during).to_frame(frame_rate)
+        }
+        during => start_frame + during.to_frame(frame_rate),
+    };
+
+    (start_frame, end_frame.max(start_frame))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_ranges() {
+        let ntsc = Rational::new(30000, 1001);
+
+        // Adjacent time ranges
+        let first = frame_range(Duration::ZERO.into(), Duration::from_secs(1).into(), ntsc);
+        let second = frame_range(
+            Duration::from_secs(1).into(),
+            Duration::from_secs(1).into(),
+            ntsc,
+        );
+        assert_eq!(first, (0, 29));
+        assert_eq!(second, (29, 59));
+
+        // Frame ranges
+        assert_eq!(frame_range(10.into(), 5.into(), ntsc), (10, 15));
+        // Start in the middle of a frame, it is included
+        let start = Duration::from_millis(1010);
+        assert_eq!(frame_range(start.into(), 2.into(), ntsc), (30, 32));
+
+        // Timecodes
+        let start = Timecode::parse("00:00:10;00", ntsc).unwrap();
+        let during = Timecode::parse("00:00:01;00", ntsc).unwrap();
+        assert_eq!(frame_range(start.into(), during.into(), ntsc), (300, 330));
+        let start = Timecode::parse("00:00:10:00", Rational::new(25, 1)).unwrap();
+        assert_eq!(frame_range(start.into(), 1.into(), ntsc), (299, 300));
+    }
+}
@@ -0,0 +1,187 @@
+use std::{fmt::Display, str::FromStr, time::Duration};
+
+/// A rational number, as used by FFMpeg for frame rates, time bases and
+/// aspect ratios (`30000/1001`, `1/90000`, `16:9`).
+///
+/// The value is always kept reduced, with a strictly positive denominator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rational {
+    num: i64,
+    den: i64,
+}
+
+impl Rational {
+    /// Creates a new rational, reduced to its simplest form.
+    /// Panics if `den` is zero.
+    pub const fn new(num: i64, den: i64) -> Self {
+        assert!(den != 0, "rational denominator cannot be zero");
+
+        let sign = if den < 0 { -1 } else { 1 };
+        let divisor = match gcd(num, den) {
+            0 => 1,
+            divisor => divisor,
+        };
+
+        Self {
+            num: sign * num / divisor,
+            den: sign * den / divisor,
+        }
+    }
+
+    /// Numerator of the rational
+    pub fn num(&self) -> i64 {
+        self.num
+    }
+
+    /// Denominator of the rational, always positive
+    pub fn den(&self) -> i64 {
+        self.den
+    }
+
+    /// Returns true if the rational is equal to zero
+    pub fn is_zero(&self) -> bool {
+        self.num == 0
+    }
+
+    /// Returns the value as a floating point number
+    pub fn as_f64(&self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+
+    /// Returns the inverse of the rational, ie the frame duration of a frame
+    /// rate. Panics if the rational is zero.
+    pub fn recip(&self) -> Self {
+        Self::new(self.den, self.num)
+    }
+
+    /// Interprets the rational as a frame rate, and returns the number of
+    /// whole frames that fit in the given duration.
+    /// Durations are only precise to the nanosecond, so a duration less than a
+    /// nanosecond away from a frame boundary is considered to be on it.
+    pub fn frames_in(&self, duration: Duration) -> u64 {
+        if self.num <= 0 {
+            return 0;
+        }
+
+        ((duration.as_nanos() + 1) * self.num as u128 / (self.den as u128 * 1_000_000_000)) as u64
+    }
+
+    /// Interprets the rational as a frame rate, and returns the time at which
+    /// the given frame starts, rounded to the nanosecond.
+    pub fn frame_time(&self, frame: u64) -> Duration {
+        if self.num <= 0 {
+            return Duration::ZERO;
+        }
+
+        let divisor = self.num as u128;
+        let nanos = (frame as u128 * self.den as u128 * 1_000_000_000 + divisor / 2) / divisor;
+
+        Duration::from_nanos(nanos as u64)
+    }
+}
+
+const fn gcd(a: i64, b: i64) -> i64 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+impl Display for Rational {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.num, self.den)
+    }
+}
+
+/// Parses `num/den` (frame rates, time bases) or `num:den` (aspect ratios).
+/// A plain integer is accepted as `num/1`.
+/// FFMpeg uses `0/0` or `N/A` for unknown values, these are rejected.
+impl FromStr for Rational {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (num, den) = s.split_once(['/', ':']).unwrap_or((s, "1"));
+        let num = num
+            .trim()
+            .parse::<i64>()
+            .map_err(|err| eyre::eyre!("invalid rational numerator {:?}: {:?}", s, err))?;
+        let den = den
+            .trim()
+            .parse::<i64>()
+            .map_err(|err| eyre::eyre!("invalid rational denominator {:?}: {:?}", s, err))?;
+
+        if den == 0 {
+            eyre::bail!("invalid rational {:?}: denominator is zero", s);
+        }
+
+        Ok(Self::new(num, den))
+    }
+}
+
+/// Serialized as `num/den`
+impl serde::Serialize for Rational {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Deserialized from a string (see `FromStr`), or an integer
+impl<'de> serde::Deserialize<'de> for Rational {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Integer(i64),
+            Text(String),
+        }
+
+        match Repr::deserialize(deserializer)? {
+            Repr::Integer(num) => Ok(Self::new(num, 1)),
+            Repr::Text(text) => text.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            "30000/1001".parse::<Rational>().unwrap(),
+            Rational::new(30000, 1001)
+        );
+        assert_eq!("16:9".parse::<Rational>().unwrap(), Rational::new(16, 9));
+        assert_eq!("50/2".parse::<Rational>().unwrap(), Rational::new(25, 1));
+        assert_eq!("24".parse::<Rational>().unwrap(), Rational::new(24, 1));
+        assert!("0/0".parse::<Rational>().is_err());
+        assert!("N/A".parse::<Rational>().is_err());
+    }
+
+    #[test]
+    fn frame_rate() {
+        let ntsc = Rational::new(30000, 1001);
+        assert_eq!(ntsc.frames_in(Duration::from_secs(10)), 299);
+        assert_eq!(ntsc.frames_in(Duration::from_nanos(10_010_000_000)), 300);
+        assert_eq!(ntsc.frame_time(300), Duration::from_nanos(10_010_000_000));
+        assert_eq!(ntsc.frame_time(1), Duration::from_nanos(33_366_667));
+        assert_eq!(ntsc.frames_in(Duration::from_nanos(33_366_666)), 1);
+        assert_eq!(ntsc.frames_in(Duration::from_nanos(33_366_665)), 0);
+        assert_eq!(ntsc.recip(), Rational::new(1001, 30000));
+
+        let film = Rational::new(24, 1);
+        assert_eq!(film.frames_in(film.frame_time(12345)), 12345);
+    }
+
+    #[test]
+    fn reduced() {
+        let rational = Rational::new(-6, -4);
+        assert_eq!((rational.num(), rational.den()), (3, 2));
+
+        let rational = Rational::new(6, -4);
+        assert_eq!((rational.num(), rational.den()), (-3, 2));
+        assert_eq!(rational.to_string(), "-3/2");
+    }
+}
@@ -0,0 +1,331 @@
+use crate::{Frame, FrameSink, ReadError};
+use image::Pixel;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// Progress of a render, reported after each frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    pub frames_done: u64,
+    /// Number of frames to render, if known
+    pub total_frames: Option<u64>,
+    pub elapsed: Duration,
+    /// Average number of frames rendered per second
+    pub fps: f64,
+    /// Estimated time left, when the number of frames is known
+    pub eta: Option<Duration>,
+    /// Size of the output so far, if the sink knows it
+    pub bytes_written: Option<u64>,
+}
+
+/// Stops a render from another thread.
+/// Clones share the same state, so that one can be kept to cancel the render
+/// the other is given to.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Asks the renders using the token to stop
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Error returned by a cancelled render, to be found with
+/// `err.downcast_ref::<Cancelled>()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("render cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Writes frames to a sink, reporting the progress and stopping when
+/// cancelled.
+///
+/// ```no_run
+/// # use movieru::{Clip, FFMpegVideoWriter, Render, CancellationToken};
+/// # use image::Rgb;
+/// # fn main() -> eyre::Result<()> {
+/// let clip = Clip::<Rgb<u8>>::from_file("in.mp4")?;
+/// let mut writer =
+///     FFMpegVideoWriter::to_file("out.mp4", clip.dimensions(), clip.frame_rate(), "rgb24")?;
+/// let token = CancellationToken::new();
+/// let frames = clip.iter_frames()?;
+/// Render::new()
+///     .on_progress(|progress| println!("{} frames", progress.frames_done))
+///     .cancellation(token.clone())
+///     .read_error(frames.read_error())
+///     .run(frames, &mut writer)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Render<'a> {
+    on_progress: Option<ProgressCallback<'a>>,
+    cancellation: Option<CancellationToken>,
+    abort_on_cancel: bool,
+    total_frames: Option<u64>,
+    read_error: Option<ReadError>,
+}
+
+type ProgressCallback<'a> = Box<dyn FnMut(&Progress) + 'a>;
+
+impl Default for Render<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Render<'a> {
+    pub fn new() -> Self {
+        Self {
+            on_progress: None,
+            cancellation: None,
+            abort_on_cancel: false,
+            total_frames: None,
+            read_error: None,
+        }
+    }
+
+    /// Calls `callback` after each frame. To follow the render from another
+    /// thread, the callback can send the progress through a channel.
+    pub fn on_progress(mut self, callback: impl FnMut(&Progress) + 'a) -> Self {
+        self.on_progress = Some(Box::new(callback));
+        self
+    }
+
+    /// Stops the render once `token` is cancelled
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// On cancellation, aborts the sink, deleting the partial output, instead
+    /// of finishing it with the frames written so far
+    pub fn abort_on_cancel(mut self, abort: bool) -> Self {
+        self.abort_on_cancel = abort;
+        self
+    }
+
+    /// Sets the number of frames to render, when the iterator does not know
+    /// it
+    pub fn total_frames(mut self, total_frames: u64) -> Self {
+        self.total_frames = Some(total_frames);
+        self
+    }
+
+    /// Fails the render when reading the frames failed, aborting the sink
+    /// instead of finishing it with the frames read before the error
+    pub fn read_error(mut self, error: ReadError) -> Self {
+        self.read_error = Some(error);
+        self
+    }
+
+    /// Writes the frames and finishes the sink.
+    /// When cancelled, decoding stops, the sink is finished or aborted, and a
+    /// `Cancelled` error is returned. The sink is aborted when a frame cannot
+    /// be written.
+    pub fn run<P, I, S>(mut self, frames: I, sink: &mut S) -> eyre::Result<()>
+    where
+        P: Pixel,
+        I: IntoIterator<Item = Frame<P>>,
+        S: FrameSink<P> + ?Sized,
+    {
+        let mut frames = frames.into_iter();
+        let total_frames = self
+            .total_frames
+            .or(frames.size_hint().1.map(|upper| upper as u64));
+        let start = Instant::now();
+        let mut frames_done = 0;
+
+        loop {
+            if self.is_cancelled() {
+                // Dropping the frames stops the decoders
+                drop(frames);
+                if self.abort_on_cancel {
+                    sink.abort()?;
+                } else {
+                    sink.finish()?;
+                }
+                return Err(Cancelled.into());
+            }
+
+            let Some(frame) = frames.next() else {
+                break;
+            };
+            if let Err(err) = sink.write(&frame) {
+                // The output is incomplete, and the writer may be stuck
+                drop(frames);
+                let _ = sink.abort();
+                return Err(err);
+            }
+            frames_done += 1;
+
+            if let Some(callback) = &mut self.on_progress {
+                let elapsed = start.elapsed();
+                let fps = frames_done as f64 / elapsed.as_secs_f64().max(f64::EPSILON);
+                let eta = total_frames.map(|total| {
+                    Duration::from_secs_f64(total.saturating_sub(frames_done) as f64 / fps)
+                });
+
+                callback(&Progress {
+                    frames_done,
+                    total_frames,
+                    elapsed,
+                    fps,
+                    eta,
+                    bytes_written: sink.bytes_written(),
+                });
+            }
+        }
+
+        if let Some(err) = self.read_error.as_ref().and_then(ReadError::take) {
+            let _ = sink.abort();
+            return Err(err);
+        }
+
+        sink.finish()
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Color, Generator, ImageSequenceWriter, Pattern, Rational, Y4mWriter};
+    use image::Rgb;
+
+    fn frames(count: u64) -> Generator<Rgb<u8>> {
+        Generator::new(
+            Pattern::Solid(Color::rgb(0, 0, 255)),
+            (4, 2),
+            Rational::new(25, 1),
+            count,
+        )
+    }
+
+    #[test]
+    fn progress() {
+        let mut sink = Y4mWriter::new(Vec::new(), (4, 2), Rational::new(25, 1));
+        let mut reports = Vec::new();
+        Render::new()
+            .on_progress(|progress| reports.push(*progress))
+            .run(frames(3), &mut sink)
+            .unwrap();
+
+        assert_eq!(reports.len(), 3);
+        let last = reports[2];
+        assert_eq!(last.frames_done, 3);
+        assert_eq!(last.total_frames, Some(3));
+        assert_eq!(last.eta, Some(Duration::ZERO));
+        assert_eq!(last.bytes_written, Some(sink.into_inner().len() as u64));
+    }
+
+    #[test]
+    fn cancel() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        let mut sink = ImageSequenceWriter::to_pattern(dir.join("%d.png"), 1).unwrap();
+
+        let token = CancellationToken::new();
+        let err = Render::new()
+            .cancellation(token.clone())
+            .on_progress(|progress| {
+                if progress.frames_done == 2 {
+                    token.cancel();
+                }
+            })
+            .run(frames(10), &mut sink)
+            .unwrap_err();
+        assert_eq!(err.downcast_ref::<Cancelled>(), Some(&Cancelled));
+        // The frames written are kept
+        assert!(dir.join("2.png").is_file());
+        assert!(!dir.join("3.png").exists());
+
+        let token = CancellationToken::new();
+        let mut sink = ImageSequenceWriter::to_pattern(dir.join("abort_%d.png"), 1).unwrap();
+        let err = Render::new()
+            .cancellation(token.clone())
+            .abort_on_cancel(true)
+            .on_progress(|_| token.cancel())
+            .run(frames(10), &mut sink)
+            .unwrap_err();
+        assert!(err.is::<Cancelled>());
+        assert!(!dir.join("abort_1.png").exists());
+    }
+
+    /// Fails on the second frame
+    #[derive(Default)]
+    struct FailingSink {
+        frames: u32,
+        aborted: bool,
+    }
+
+    impl FrameSink<Rgb<u8>> for FailingSink {
+        fn write(&mut self, _: &Frame<Rgb<u8>>) -> eyre::Result<()> {
+            self.frames += 1;
+            if self.frames == 2 {
+                eyre::bail!("disk full");
+            }
+            Ok(())
+        }
+
+        fn abort(&mut self) -> eyre::Result<()> {
+            self.aborted = true;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_error() {
+        let mut sink = FailingSink::default();
+        let err = Render::new().run(frames(10), &mut sink).unwrap_err();
+        assert_eq!(err.to_string(), "disk full");
+        assert_eq!(sink.frames, 2);
+        assert!(sink.aborted);
+    }
+
+    #[test]
+    fn read_error() {
+        let error = ReadError::new();
+        error.set(eyre::eyre!("cannot read frame 1"));
+        let mut sink = FailingSink::default();
+        let err = Render::new()
+            .read_error(error.clone())
+            .run(frames(1), &mut sink)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "cannot read frame 1");
+        assert!(sink.aborted);
+        assert!(error.take().is_none());
+
+        // Without error, the sink is finished
+        let mut sink = FailingSink::default();
+        Render::new()
+            .read_error(error)
+            .run(frames(1), &mut sink)
+            .unwrap();
+        assert!(!sink.aborted);
+    }
+}
@@ -0,0 +1,138 @@
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+/// The file name pattern of a numbered image sequence, as understood by
+/// FFMpeg: `frame_%05d.png` for `frame_00001.png`, `frame_%d.png` for
+/// `frame_1.png`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SequencePattern {
+    dir: PathBuf,
+    prefix: String,
+    width: usize, // 0 if the numbers are not padded
+    suffix: String,
+}
+
+impl SequencePattern {
+    pub(crate) fn parse(pattern: &Path) -> eyre::Result<Self> {
+        let name = pattern
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or(eyre::eyre!("invalid image sequence pattern {:?}", pattern))?;
+        let (prefix, rest) = name.split_once('%').ok_or(eyre::eyre!(
+            "image sequence pattern {:?} has no %d",
+            pattern
+        ))?;
+        let (width, suffix) = rest.split_once('d').ok_or(eyre::eyre!(
+            "image sequence pattern {:?} has no %d",
+            pattern
+        ))?;
+        let width = match width {
+            "" => 0,
+            width if width.starts_with('0') => width.parse().map_err(|_| {
+                eyre::eyre!("invalid padding in image sequence pattern {:?}", pattern)
+            })?,
+            _ => eyre::bail!(
+                "invalid padding in image sequence pattern {:?}, expected %0Nd",
+                pattern
+            ),
+        };
+        if suffix.contains('%') {
+            eyre::bail!("image sequence pattern {:?} has several %", pattern);
+        }
+
+        Ok(Self {
+            dir: pattern.parent().map(Path::to_path_buf).unwrap_or_default(),
+            prefix: prefix.to_string(),
+            width,
+            suffix: suffix.to_string(),
+        })
+    }
+
+    /// Returns the path of the image with the given number
+    pub(crate) fn path(&self, number: u64) -> PathBuf {
+        self.dir.join(format!(
+            "{}{:0width$}{}",
+            self.prefix,
+            number,
+            self.suffix,
+            width = self.width
+        ))
+    }
+
+    /// Returns the number of the image file name, if it matches the pattern
+    fn number(&self, name: &str) -> Option<u64> {
+        let digits = name
+            .strip_prefix(&self.prefix)?
+            .strip_suffix(&self.suffix)?;
+        if digits.is_empty() || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+            return None;
+        }
+
+        let number = digits.parse().ok()?;
+        (format!("{:0width$}", number, width = self.width) == digits).then_some(number)
+    }
+
+    /// Returns the first number of the sequence, and its number of images.
+    /// As FFMpeg, the sequence starts at the lowest number found, and stops at
+    /// the first missing one.
+    pub(crate) fn find_images(&self) -> eyre::Result<(u64, usize)> {
+        let dir = if self.dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            &self.dir
+        };
+        let numbers = std::fs::read_dir(dir)
+            .map_err(|err| eyre::eyre!("cannot read directory {:?}: {}", dir, err))?
+            .filter_map(|entry| self.number(entry.ok()?.file_name().to_str()?))
+            .collect::<BTreeSet<_>>();
+
+        let start = *numbers.first().ok_or(eyre::eyre!(
+            "no image found for the sequence {:?}",
+            self.path(0)
+        ))?;
+        let count = (start..)
+            .take_while(|number| numbers.contains(number))
+            .count();
+
+        Ok((start, count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns() {
+        let pattern = SequencePattern::parse(Path::new("renders/frame_%05d.png")).unwrap();
+        assert_eq!(pattern.path(42), Path::new("renders/frame_00042.png"));
+        assert_eq!(pattern.number("frame_00042.png"), Some(42));
+        assert_eq!(pattern.number("frame_123456.png"), Some(123456));
+        assert_eq!(pattern.number("frame_0042.png"), None);
+        assert_eq!(pattern.number("frame_00042.jpg"), None);
+
+        let pattern = SequencePattern::parse(Path::new("%d.tif")).unwrap();
+        assert_eq!(pattern.path(7), Path::new("7.tif"));
+        assert_eq!(pattern.number("07.tif"), None);
+
+        assert!(SequencePattern::parse(Path::new("frame.png")).is_err());
+        assert!(SequencePattern::parse(Path::new("frame_%5d.png")).is_err());
+        assert!(SequencePattern::parse(Path::new("%d_%d.png")).is_err());
+    }
+
+    #[test]
+    fn find_images() {
+        let dir = tempfile::tempdir().unwrap();
+        let pattern = SequencePattern::parse(&dir.path().join("img_%03d.png")).unwrap();
+        for number in [3, 4, 5, 6, 8] {
+            std::fs::write(pattern.path(number), b"").unwrap();
+        }
+        std::fs::write(dir.path().join("img_0007.png"), b"").unwrap();
+
+        assert_eq!(pattern.find_images().unwrap(), (3, 4));
+        dir.close().unwrap();
+        assert!(pattern.find_images().is_err());
+    }
+}
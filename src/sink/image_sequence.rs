@@ -0,0 +1,146 @@
+use crate::{sequence::SequencePattern, Frame, FrameSink};
+use image::{DynamicImage, ImageBuffer, ImageFormat, Pixel};
+use std::path::Path;
+
+/// Writes frames as numbered image files, one file per frame.
+/// Frames are converted to what the format supports: EXR files are written in
+/// 32 bits floats, JPEG files in 8 bits without alpha.
+#[derive(Debug, Clone)]
+pub struct ImageSequenceWriter {
+    pattern: SequencePattern,
+    format: ImageFormat,
+    start_number: u64,
+    number: u64, // number of the next file
+    bytes: u64,
+}
+
+impl ImageSequenceWriter {
+    /// Writes the frames to `<dir>/<prefix><number>.<extension>`, the number
+    /// being padded with zeros to `padding` digits.
+    /// The directory is created if needed.
+    pub fn to_dir(
+        dir: impl AsRef<Path>,
+        prefix: &str,
+        padding: usize,
+        start_number: u64,
+        format: ImageFormat,
+    ) -> eyre::Result<Self> {
+        let extension = format
+            .extensions_str()
+            .first()
+            .ok_or(eyre::eyre!("no file extension for {:?}", format))?;
+        std::fs::create_dir_all(dir.as_ref())
+            .map_err(|err| eyre::eyre!("cannot create {:?}: {}", dir.as_ref(), err))?;
+
+        Self::new(
+            &dir.as_ref()
+                .join(format!("{}%0{}d.{}", prefix, padding, extension)),
+            start_number,
+            format,
+        )
+    }
+
+    /// Writes the frames to files named after a pattern, ie
+    /// `renders/frame_%05d.png`, the format being deduced from the extension.
+    pub fn to_pattern(pattern: impl AsRef<Path>, start_number: u64) -> eyre::Result<Self> {
+        let format = ImageFormat::from_path(pattern.as_ref())
+            .map_err(|err| eyre::eyre!("unknown image format {:?}: {}", pattern.as_ref(), err))?;
+
+        Self::new(pattern.as_ref(), start_number, format)
+    }
+
+    fn new(pattern: &Path, start_number: u64, format: ImageFormat) -> eyre::Result<Self> {
+        if !format.writing_enabled() {
+            eyre::bail!("cannot write {:?} images", format);
+        }
+
+        Ok(Self {
+            pattern: SequencePattern::parse(pattern)?,
+            format,
+            start_number,
+            number: start_number,
+            bytes: 0,
+        })
+    }
+}
+
+impl<P> FrameSink<P> for ImageSequenceWriter
+where
+    P: Pixel,
+    DynamicImage: From<ImageBuffer<P, Vec<P::Subpixel>>>,
+{
+    fn write(&mut self, frame: &Frame<P>) -> eyre::Result<()> {
+        let image = DynamicImage::from(frame.image().clone());
+        let image = match self.format {
+            ImageFormat::OpenExr if image.color().has_alpha() => image.to_rgba32f().into(),
+            ImageFormat::OpenExr => image.to_rgb32f().into(),
+            ImageFormat::Jpeg if image.color().has_color() => image.to_rgb8().into(),
+            ImageFormat::Jpeg => image.to_luma8().into(),
+            _ => image,
+        };
+
+        let path = self.pattern.path(self.number);
+        image
+            .save_with_format(&path, self.format)
+            .map_err(|err| eyre::eyre!("cannot write {:?}: {}", path, err))?;
+        self.bytes += std::fs::metadata(&path).map_or(0, |metadata| metadata.len());
+        self.number += 1;
+
+        Ok(())
+    }
+
+    /// Deletes the files written so far
+    fn abort(&mut self) -> eyre::Result<()> {
+        for number in self.start_number..self.number {
+            let path = self.pattern.path(number);
+            std::fs::remove_file(&path)
+                .map_err(|err| eyre::eyre!("cannot delete {:?}: {}", path, err))?;
+        }
+        self.number = self.start_number;
+        self.bytes = 0;
+
+        Ok(())
+    }
+
+    fn bytes_written(&self) -> Option<u64> {
+        Some(self.bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, Rgba};
+
+    #[test]
+    fn write() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        // Created by the writer
+        let dir = temp_dir.path().join("sink");
+        let frames = (0..3u16)
+            .map(|index| Frame::<Rgb<u16>>::from_vec(vec![index * 1000 + 1; 4 * 2 * 3], (4, 2)));
+
+        let mut sink =
+            ImageSequenceWriter::to_dir(&dir, "frame_", 4, 10, ImageFormat::Png).unwrap();
+        sink.write_all(frames).unwrap();
+        for index in 0..3 {
+            let image = image::open(dir.join(format!("frame_{:04}.png", index + 10))).unwrap();
+            assert_eq!(
+                image.into_rgb16().get_pixel(3, 1),
+                &Rgb([index * 1000 + 1; 3])
+            );
+        }
+
+        // Alpha is dropped in JPEG, EXR are floats
+        let frame = Frame::<Rgba<u8>>::from_vec(vec![255; 4 * 2 * 4], (4, 2));
+        let mut sink = ImageSequenceWriter::to_pattern(dir.join("%d.jpg"), 1).unwrap();
+        sink.write(&frame).unwrap();
+        let mut sink = ImageSequenceWriter::to_pattern(dir.join("%d.exr"), 1).unwrap();
+        sink.write(&frame).unwrap();
+        let image = image::open(dir.join("1.exr")).unwrap().into_rgba32f();
+        assert_eq!(image.get_pixel(0, 0), &Rgba([1.0; 4]));
+        assert!(dir.join("1.jpg").is_file());
+
+        assert!(ImageSequenceWriter::to_pattern(dir.join("%d.unknown"), 1).is_err());
+    }
+}
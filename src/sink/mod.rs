@@ -0,0 +1,42 @@
+use crate::Frame;
+use image::Pixel;
+
+mod image_sequence;
+pub use self::image_sequence::ImageSequenceWriter;
+
+mod y4m;
+pub use self::y4m::Y4mWriter;
+
+/// Destination of frames: an encoded video, an image sequence, a raw stream.
+pub trait FrameSink<P: Pixel> {
+    /// Writes the next frame
+    fn write(&mut self, frame: &Frame<P>) -> eyre::Result<()>;
+
+    /// Flushes and closes the output, once every frame has been written
+    fn finish(&mut self) -> eyre::Result<()> {
+        Ok(())
+    }
+
+    /// Stops writing without finishing the output, deleting what was written
+    /// when possible. Outputs that cannot be deleted are finished as is.
+    fn abort(&mut self) -> eyre::Result<()> {
+        self.finish()
+    }
+
+    /// Returns the number of bytes written so far, if known
+    fn bytes_written(&self) -> Option<u64> {
+        None
+    }
+
+    /// Writes all the frames, then finishes the output
+    fn write_all(&mut self, frames: impl IntoIterator<Item = Frame<P>>) -> eyre::Result<()>
+    where
+        Self: Sized,
+    {
+        for frame in frames {
+            self.write(&frame)?;
+        }
+
+        self.finish()
+    }
+}
@@ -0,0 +1,220 @@
+use crate::{Frame, FrameSink, Rational};
+use image::{Pixel, Primitive};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+/// Writes frames to a raw YUV4MPEG2 (`.y4m`) stream, without FFMpeg.
+/// Gray frames are written as is, in full range (`Cmono`), color frames are
+/// converted to BT.601 limited range YUV 4:4:4 (`C444`). The range is stored
+/// in the header as `XCOLORRANGE`. 16 bits frames are written with 16 bits
+/// samples, alpha channels are dropped.
+#[derive(Debug)]
+pub struct Y4mWriter<W: Write> {
+    writer: W,
+    dimensions: (u32, u32),
+    frame_rate: Rational,
+    header_written: bool, // the header depends on the first frame pixels
+    bytes: u64,
+}
+
+impl Y4mWriter<BufWriter<File>> {
+    /// Writes the stream to a file
+    pub fn to_file(
+        path: impl AsRef<Path>,
+        dimensions: (u32, u32),
+        frame_rate: Rational,
+    ) -> eyre::Result<Self> {
+        let file = File::create(path.as_ref())
+            .map_err(|err| eyre::eyre!("cannot create {:?}: {}", path.as_ref(), err))?;
+
+        Ok(Self::new(BufWriter::new(file), dimensions, frame_rate))
+    }
+}
+
+impl<W: Write> Y4mWriter<W> {
+    /// Writes the stream to the given writer
+    pub fn new(writer: W, dimensions: (u32, u32), frame_rate: Rational) -> Self {
+        Self {
+            writer,
+            dimensions,
+            frame_rate,
+            header_written: false,
+            bytes: 0,
+        }
+    }
+
+    /// Returns the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Y4M colorspace and samples of a pixel type
+struct Layout {
+    gray: bool,
+    wide: bool, // 16 bits samples
+    max: f32,   // maximum value of a subpixel
+}
+
+impl Layout {
+    fn of<P: Pixel>() -> Self
+    where
+        P::Subpixel: Into<f32>,
+    {
+        let max: f32 = P::Subpixel::DEFAULT_MAX_VALUE.into();
+
+        Self {
+            gray: P::COLOR_MODEL.starts_with('Y'),
+            wide: max > 255.0,
+            max,
+        }
+    }
+
+    fn colorspace(&self) -> &'static str {
+        match (self.gray, self.wide) {
+            (true, false) => "mono",
+            (true, true) => "mono16",
+            (false, false) => "444",
+            (false, true) => "444p16",
+        }
+    }
+
+    /// Gray is full range, color is limited range
+    fn range(&self) -> &'static str {
+        if self.gray {
+            "FULL"
+        } else {
+            "LIMITED"
+        }
+    }
+
+    /// Appends a sample, given in `[0, 255]` whatever the sample size.
+    /// Full range samples are stretched to `[0, 65535]`, while limited range
+    /// ones are shifted, so that the 16-235 range becomes 4096-60160.
+    fn push(&self, plane: &mut Vec<u8>, sample: f32) {
+        if self.wide {
+            let scale = if self.gray { 257.0 } else { 256.0 };
+            let sample = (sample * scale).round().clamp(0.0, 65535.0) as u16;
+            plane.extend(sample.to_le_bytes());
+        } else {
+            plane.push(sample.round().clamp(0.0, 255.0) as u8);
+        }
+    }
+}
+
+impl<P, W> FrameSink<P> for Y4mWriter<W>
+where
+    P: Pixel,
+    P::Subpixel: Into<f32>,
+    W: Write,
+{
+    fn write(&mut self, frame: &Frame<P>) -> eyre::Result<()> {
+        let image = frame.image();
+        if image.dimensions() != self.dimensions {
+            eyre::bail!(
+                "frame is {:?}, the stream is {:?}",
+                image.dimensions(),
+                self.dimensions
+            );
+        }
+
+        let layout = Layout::of::<P>();
+        if !self.header_written {
+            let (width, height) = self.dimensions;
+            let header = format!(
+                "YUV4MPEG2 W{} H{} F{}:{} Ip A1:1 C{} XCOLORRANGE={}\n",
+                width,
+                height,
+                self.frame_rate.num(),
+                self.frame_rate.den(),
+                layout.colorspace(),
+                layout.range()
+            );
+            self.writer.write_all(header.as_bytes())?;
+            self.bytes += header.len() as u64;
+            self.header_written = true;
+        }
+
+        let scale = 255.0 / layout.max;
+        let len = image.width() as usize * image.height() as usize;
+        let mut planes = vec![Vec::with_capacity(len); if layout.gray { 1 } else { 3 }];
+        for pixel in image.pixels() {
+            if layout.gray {
+                layout.push(&mut planes[0], pixel.to_luma()[0].into() * scale);
+                continue;
+            }
+
+            let [r, g, b] = pixel.to_rgb().0.map(|value| value.into() / layout.max);
+            let y = 0.299 * r + 0.587 * g + 0.114 * b;
+            layout.push(&mut planes[0], 16.0 + 219.0 * y);
+            layout.push(&mut planes[1], 128.0 + 224.0 * (b - y) / 1.772);
+            layout.push(&mut planes[2], 128.0 + 224.0 * (r - y) / 1.402);
+        }
+
+        self.writer.write_all(b"FRAME\n")?;
+        self.bytes += 6;
+        for plane in planes {
+            self.writer.write_all(&plane)?;
+            self.bytes += plane.len() as u64;
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> eyre::Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn bytes_written(&self) -> Option<u64> {
+        Some(self.bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Luma, Rgb};
+
+    #[test]
+    fn write() {
+        let mut sink = Y4mWriter::new(Vec::new(), (2, 1), Rational::new(30000, 1001));
+        let frame = Frame::<Luma<u8>>::from_vec(vec![0, 200], (2, 1));
+        sink.write_all([frame]).unwrap();
+        assert_eq!(
+            sink.into_inner(),
+            b"YUV4MPEG2 W2 H1 F30000:1001 Ip A1:1 Cmono XCOLORRANGE=FULL\nFRAME\n\x00\xc8"
+        );
+
+        let mut sink = Y4mWriter::new(Vec::new(), (2, 1), Rational::new(25, 1));
+        let frame = Frame::<Rgb<u16>>::from_vec(vec![65535, 65535, 65535, 0, 0, 0], (2, 1));
+        sink.write(&frame).unwrap();
+        sink.write(&frame).unwrap();
+        let header = b"YUV4MPEG2 W2 H1 F25:1 Ip A1:1 C444p16 XCOLORRANGE=LIMITED\n";
+        // 235 << 8 and 16 << 8, little-endian
+        let frame = [
+            &b"FRAME\n"[..],
+            &[0x00, 0xeb, 0x00, 0x10],
+            &[0x00, 0x80, 0x00, 0x80],
+            &[0x00, 0x80, 0x00, 0x80],
+        ]
+        .concat();
+        let stream = sink.into_inner();
+        assert!(stream.starts_with(header));
+        assert_eq!(&stream[header.len()..], [&frame[..], &frame[..]].concat());
+
+        // 16 bits gray stays full range
+        let mut sink = Y4mWriter::new(Vec::new(), (2, 1), Rational::new(25, 1));
+        let frame = Frame::<Luma<u16>>::from_vec(vec![65535, 0], (2, 1));
+        sink.write(&frame).unwrap();
+        assert!(sink.into_inner().ends_with(b"FRAME\n\xff\xff\x00\x00"));
+
+        let mut sink = Y4mWriter::new(Vec::new(), (4, 4), Rational::new(25, 1));
+        assert!(sink
+            .write(&Frame::<Luma<u8>>::from_vec(vec![0], (1, 1)))
+            .is_err());
+    }
+}
@@ -0,0 +1,265 @@
+use crate::{font, Clip, Frame, Thumbnails};
+use image::{ImageBuffer, Rgb};
+use std::{fmt::Write, path::Path, time::Duration};
+
+/// Settings of a contact sheet: thumbnails sampled evenly across a clip and
+/// tiled into a single image, row by row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContactSheet {
+    /// Number of thumbnails
+    pub count: usize,
+    /// Number of thumbnails per row
+    pub columns: u32,
+    /// Width of a thumbnail, its height follows the aspect ratio of the clip
+    pub thumbnail_width: u32,
+    /// Space between thumbnails, and around the grid
+    pub padding: u32,
+    /// Draw the time of each thumbnail in its bottom left corner
+    pub labels: bool,
+    /// Decode the exact frame at each time instead of the keyframe before it.
+    /// This is slower, but avoids repeated thumbnails when keyframes are far
+    /// apart.
+    pub accurate: bool,
+}
+
+impl Default for ContactSheet {
+    fn default() -> Self {
+        Self {
+            count: 100,
+            columns: 10,
+            thumbnail_width: 160,
+            padding: 0,
+            labels: false,
+            accurate: false,
+        }
+    }
+}
+
+/// A thumbnail in a sprite, shown for the times `[start, end)` of the clip
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpriteCue {
+    pub start: Duration,
+    pub end: Duration,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A contact sheet image, with the position of each thumbnail
+pub struct Sprite {
+    pub image: Frame<Rgb<u8>>,
+    pub cues: Vec<SpriteCue>,
+}
+
+impl ContactSheet {
+    /// Samples the thumbnails of the clip, and tiles them into a sprite.
+    /// Each thumbnail is taken at the middle of the part of the clip it
+    /// stands for.
+    pub fn generate<P>(&self, clip: &Clip<P>) -> eyre::Result<Sprite> {
+        if self.columns == 0 || self.thumbnail_width == 0 {
+            eyre::bail!("contact sheet needs at least one column and a thumbnail width");
+        }
+
+        let (width, height) = clip.dimensions();
+        let thumbnail_height = ((self.thumbnail_width as u64 * height as u64 + width as u64 / 2)
+            / width as u64)
+            .max(1) as u32;
+        let clip = clip
+            .cast::<Rgb<u8>>()
+            .with_dimensions((self.thumbnail_width, thumbnail_height));
+
+        let thumbnails = if self.accurate {
+            Thumbnails::Count(self.count)
+                .times(clip.duration())?
+                .into_iter()
+                .map(|time| Ok((time, clip.frame_at(time)?)))
+                .collect::<eyre::Result<Vec<_>>>()?
+        } else {
+            clip.thumbnails(self.count)?
+        };
+
+        Ok(self.tile(thumbnails, clip.duration()))
+    }
+
+    /// Tiles the thumbnails of a clip lasting `duration`
+    fn tile(&self, thumbnails: Vec<(Duration, Frame<Rgb<u8>>)>, duration: Duration) -> Sprite {
+        let (width, height) = thumbnails
+            .first()
+            .map(|(_, thumbnail)| thumbnail.image().dimensions())
+            .unwrap_or_default();
+        let count = thumbnails.len() as u32;
+        let columns = self.columns.min(count).max(1);
+        let rows = count.div_ceil(columns);
+        let sheet_dimensions = (
+            columns * (width + self.padding) + self.padding,
+            rows * (height + self.padding) + self.padding,
+        );
+        let mut image = ImageBuffer::new(sheet_dimensions.0, sheet_dimensions.1);
+        // Labels are about a tenth of the thumbnail height
+        let scale = (height / 10 / font::GLYPH_HEIGHT).max(1);
+
+        let cues = thumbnails
+            .into_iter()
+            .enumerate()
+            .map(|(index, (time, mut thumbnail))| {
+                let index = index as u32;
+                let x = self.padding + index % columns * (width + self.padding);
+                let y = self.padding + index / columns * (height + self.padding);
+
+                if self.labels {
+                    let label = label(time);
+                    let (_, label_height) = font::text_size(&label, scale);
+                    font::draw_text(
+                        thumbnail.image_mut(),
+                        (2 * scale, height.saturating_sub(label_height + 2 * scale)),
+                        scale,
+                        &label,
+                        Rgb([255, 255, 255]),
+                        Some(Rgb([0, 0, 0])),
+                    );
+                }
+                image::imageops::replace(&mut image, thumbnail.image(), x as i64, y as i64);
+
+                SpriteCue {
+                    start: duration * index / count,
+                    end: duration * (index + 1) / count,
+                    x,
+                    y,
+                    width,
+                    height,
+                }
+            })
+            .collect();
+
+        Sprite {
+            image: Frame::from_vec(image.into_raw(), sheet_dimensions),
+            cues,
+        }
+    }
+}
+
+impl Sprite {
+    /// Returns the WebVTT thumbnail track, where `url` is the location of the
+    /// sprite image as seen by the player
+    pub fn webvtt(&self, url: &str) -> String {
+        let mut vtt = String::from("WEBVTT\n");
+        for cue in &self.cues {
+            let _ = write!(
+                vtt,
+                "\n{} --> {}\n{}#xywh={},{},{},{}\n",
+                vtt_time(cue.start),
+                vtt_time(cue.end),
+                url,
+                cue.x,
+                cue.y,
+                cue.width,
+                cue.height
+            );
+        }
+
+        vtt
+    }
+
+    /// Saves the sprite image, and its WebVTT track referring to the image by
+    /// its file name
+    pub fn save(
+        &self,
+        image_path: impl AsRef<Path>,
+        vtt_path: impl AsRef<Path>,
+    ) -> eyre::Result<()> {
+        let image_path = image_path.as_ref();
+        let url = image_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or(eyre::eyre!("invalid sprite image path {:?}", image_path))?;
+
+        self.image.save(image_path)?;
+        std::fs::write(vtt_path.as_ref(), self.webvtt(url))
+            .map_err(|err| eyre::eyre!("cannot write {:?}: {}", vtt_path.as_ref(), err))
+    }
+}
+
+/// Formats a time as `HH:MM:SS.mmm`
+fn vtt_time(time: Duration) -> String {
+    let seconds = time.as_secs();
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        time.subsec_millis()
+    )
+}
+
+/// Formats a time as `MM:SS`, or `H:MM:SS` past one hour
+fn label(time: Duration) -> String {
+    let seconds = time.as_secs();
+    match seconds / 3600 {
+        0 => format!("{:02}:{:02}", seconds / 60, seconds % 60),
+        hours => format!("{}:{:02}:{:02}", hours, seconds / 60 % 60, seconds % 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thumbnail(value: u8) -> Frame<Rgb<u8>> {
+        Frame::from_vec(vec![value; 16 * 9 * 3], (16, 9))
+    }
+
+    #[test]
+    fn tile() {
+        let sheet = ContactSheet {
+            columns: 2,
+            padding: 1,
+            ..Default::default()
+        };
+        let thumbnails = (0..3)
+            .map(|index| {
+                (
+                    Duration::from_secs(index * 2 + 1),
+                    thumbnail(index as u8 + 1),
+                )
+            })
+            .collect();
+        let sprite = sheet.tile(thumbnails, Duration::from_secs(6));
+
+        let image = sprite.image.image();
+        assert_eq!(image.dimensions(), (35, 21));
+        assert_eq!(image.get_pixel(0, 0), &Rgb([0, 0, 0]));
+        assert_eq!(image.get_pixel(1, 1), &Rgb([1, 1, 1]));
+        assert_eq!(image.get_pixel(18, 1), &Rgb([2, 2, 2]));
+        assert_eq!(image.get_pixel(16, 19), &Rgb([3, 3, 3]));
+        assert_eq!(image.get_pixel(18, 19), &Rgb([0, 0, 0]));
+
+        assert_eq!(
+            sprite.webvtt("sprite.jpg"),
+            "WEBVTT\n\
+             \n00:00:00.000 --> 00:00:02.000\nsprite.jpg#xywh=1,1,16,9\n\
+             \n00:00:02.000 --> 00:00:04.000\nsprite.jpg#xywh=18,1,16,9\n\
+             \n00:00:04.000 --> 00:00:06.000\nsprite.jpg#xywh=1,11,16,9\n"
+        );
+    }
+
+    #[test]
+    fn labels() {
+        assert_eq!(label(Duration::from_millis(83_900)), "01:23");
+        assert_eq!(label(Duration::from_secs(3725)), "1:02:05");
+
+        let sheet = ContactSheet {
+            labels: true,
+            ..Default::default()
+        };
+        let sprite = sheet.tile(
+            vec![(Duration::ZERO, thumbnail(128))],
+            Duration::from_secs(1),
+        );
+        let image = sprite.image.image();
+        // Background box, then the first column of "0"
+        assert_eq!(image.get_pixel(1, 2), &Rgb([0, 0, 0]));
+        assert_eq!(image.get_pixel(2, 2), &Rgb([255, 255, 255]));
+        assert_eq!(image.get_pixel(15, 0), &Rgb([128, 128, 128]));
+    }
+}
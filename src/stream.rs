@@ -0,0 +1,51 @@
+/// Which stream of a file to use, when it has several of a kind, ie
+/// multi-angle Matroska files or videos with an embedded cover art.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum StreamSelector {
+    /// The first stream, skipping cover art (attached pictures) when there
+    /// is another one
+    #[default]
+    Default,
+    /// The stream of the given index in the container, as in `ClipMetadata`
+    Index(u32),
+    /// The n-th stream of the kind, from 0, as FFMpeg's `0:v:n`
+    Nth(usize),
+}
+
+impl StreamSelector {
+    /// Returns the index in the container of the selected stream, among the
+    /// (index, is an attached picture) of the streams of the kind
+    pub(crate) fn find(&self, streams: &[(u32, bool)]) -> eyre::Result<u32> {
+        let found = match *self {
+            Self::Default => streams
+                .iter()
+                .find(|(_, attached_picture)| !attached_picture)
+                .or(streams.first()),
+            Self::Index(index) => streams.iter().find(|(stream, _)| *stream == index),
+            Self::Nth(nth) => streams.get(nth),
+        };
+
+        found
+            .map(|(index, _)| *index)
+            .ok_or(eyre::eyre!("no stream matches {:?}", self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find() {
+        // A cover art, then two angles
+        let streams = [(0, true), (1, false), (3, false)];
+        assert_eq!(StreamSelector::Default.find(&streams).unwrap(), 1);
+        assert_eq!(StreamSelector::Index(3).find(&streams).unwrap(), 3);
+        assert_eq!(StreamSelector::Nth(0).find(&streams).unwrap(), 0);
+        assert!(StreamSelector::Index(2).find(&streams).is_err());
+        assert!(StreamSelector::Nth(3).find(&streams).is_err());
+
+        assert_eq!(StreamSelector::Default.find(&[(0, true)]).unwrap(), 0);
+        assert!(StreamSelector::Default.find(&[]).is_err());
+    }
+}
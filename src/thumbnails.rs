@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+/// How to space the thumbnails of a clip
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Thumbnails {
+    /// A given number of thumbnails, evenly spread over the clip.
+    /// Each one is taken at the middle of its part of the clip, so that the
+    /// first and last frames (often black) are avoided.
+    Count(usize),
+    /// A thumbnail every given duration, starting at the first frame
+    Interval(Duration),
+}
+
+impl Thumbnails {
+    /// Returns the times of the thumbnails in a clip lasting `duration`
+    pub(crate) fn times(&self, duration: Duration) -> eyre::Result<Vec<Duration>> {
+        match *self {
+            Self::Count(count) => Ok((0..count as u32)
+                .map(|index| duration * (2 * index + 1) / (2 * count as u32))
+                .collect()),
+            Self::Interval(interval) if interval.is_zero() => {
+                eyre::bail!("thumbnails interval cannot be zero")
+            }
+            Self::Interval(interval) => Ok((0..)
+                .map(|index| interval * index)
+                .take_while(|time| *time < duration)
+                .collect()),
+        }
+    }
+}
+
+impl From<usize> for Thumbnails {
+    fn from(count: usize) -> Self {
+        Self::Count(count)
+    }
+}
+
+impl From<Duration> for Thumbnails {
+    fn from(interval: Duration) -> Self {
+        Self::Interval(interval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn times() {
+        let duration = Duration::from_secs(10);
+        assert_eq!(
+            Thumbnails::Count(4).times(duration).unwrap(),
+            [1250, 3750, 6250, 8750].map(Duration::from_millis)
+        );
+        assert!(Thumbnails::Count(0).times(duration).unwrap().is_empty());
+        assert_eq!(
+            Thumbnails::Interval(Duration::from_secs(3))
+                .times(duration)
+                .unwrap(),
+            [0, 3, 6, 9].map(Duration::from_secs)
+        );
+        assert!(Thumbnails::Interval(Duration::ZERO)
+            .times(duration)
+            .is_err());
+    }
+}
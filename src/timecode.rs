@@ -0,0 +1,323 @@
+use crate::Rational;
+use std::{
+    fmt::Display,
+    ops::{Add, Sub},
+    time::Duration,
+};
+
+/// A SMPTE timecode, `HH:MM:SS:FF`, tied to a frame rate.
+///
+/// Drop-frame timecodes (`HH:MM:SS;FF`) are supported for 29.97 and 59.94 fps:
+/// frame numbers 0 and 1 (0 to 3 for 59.94) are skipped at the start of each
+/// minute, except every tenth minute, so that the timecode stays in sync with
+/// the wall clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Timecode {
+    frame: u64, // number of frames since 00:00:00:00
+    frame_rate: Rational,
+    drop_frame: bool,
+}
+
+impl Timecode {
+    /// Creates a timecode from its components.
+    /// Fails if a component is out of range, if the frame number is skipped in
+    /// drop-frame, or if drop-frame is not supported by the frame rate.
+    pub fn new(
+        hours: u32,
+        minutes: u32,
+        seconds: u32,
+        frames: u32,
+        frame_rate: Rational,
+        drop_frame: bool,
+    ) -> eyre::Result<Self> {
+        let nominal = nominal_fps(frame_rate)?;
+        let dropped = dropped_frames(frame_rate, drop_frame)?;
+
+        if minutes >= 60 || seconds >= 60 || frames as u64 >= nominal {
+            eyre::bail!(
+                "invalid timecode {:02}:{:02}:{:02}:{:02} at {} fps",
+                hours,
+                minutes,
+                seconds,
+                frames,
+                frame_rate
+            );
+        }
+        if dropped > 0 && seconds == 0 && !minutes.is_multiple_of(10) && (frames as u64) < dropped {
+            eyre::bail!(
+                "invalid drop-frame timecode {:02}:{:02}:{:02};{:02}: frame is skipped",
+                hours,
+                minutes,
+                seconds,
+                frames
+            );
+        }
+
+        let total_minutes = hours as u64 * 60 + minutes as u64;
+        let frame = (total_minutes * 60 + seconds as u64) * nominal + frames as u64
+            - dropped * (total_minutes - total_minutes / 10);
+
+        Ok(Self {
+            frame,
+            frame_rate,
+            drop_frame: dropped > 0,
+        })
+    }
+
+    /// Creates a timecode from a number of frames since 00:00:00:00
+    pub fn from_frame(frame: u64, frame_rate: Rational, drop_frame: bool) -> eyre::Result<Self> {
+        nominal_fps(frame_rate)?;
+        let dropped = dropped_frames(frame_rate, drop_frame)?;
+
+        Ok(Self {
+            frame,
+            frame_rate,
+            drop_frame: dropped > 0,
+        })
+    }
+
+    /// Creates the timecode of the frame displayed at the given time
+    pub fn from_duration(
+        duration: Duration,
+        frame_rate: Rational,
+        drop_frame: bool,
+    ) -> eyre::Result<Self> {
+        Self::from_frame(frame_rate.frames_in(duration), frame_rate, drop_frame)
+    }
+
+    /// Parses `HH:MM:SS:FF`, or `HH:MM:SS;FF` (also `HH:MM:SS.FF` and
+    /// `HH;MM;SS;FF`) for drop-frame.
+    pub fn parse(timecode: &str, frame_rate: Rational) -> eyre::Result<Self> {
+        let drop_frame = timecode.contains([';', '.']);
+        let components = timecode
+            .split([':', ';', '.'])
+            .map(|component| component.trim().parse::<u32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| eyre::eyre!("invalid timecode {:?}: {:?}", timecode, err))?;
+
+        let [hours, minutes, seconds, frames] = components[..] else {
+            eyre::bail!("invalid timecode {:?}: expected HH:MM:SS:FF", timecode);
+        };
+
+        Self::new(hours, minutes, seconds, frames, frame_rate, drop_frame)
+    }
+
+    /// Returns the number of frames since 00:00:00:00
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Returns the time at which the frame starts, since 00:00:00:00.
+    /// In drop-frame, this is close to the time shown by the timecode, in
+    /// non-drop-frame at 29.97 fps it drifts by 3.6 seconds per hour.
+    pub fn to_duration(&self) -> Duration {
+        self.frame_rate.frame_time(self.frame)
+    }
+
+    pub fn frame_rate(&self) -> Rational {
+        self.frame_rate
+    }
+
+    pub fn is_drop_frame(&self) -> bool {
+        self.drop_frame
+    }
+
+    /// Returns the (hours, minutes, seconds, frames) components
+    pub fn components(&self) -> (u32, u32, u32, u32) {
+        let nominal = nominal_fps(self.frame_rate).expect("checked at creation");
+        let dropped =
+            dropped_frames(self.frame_rate, self.drop_frame).expect("checked at creation");
+
+        // Add back the skipped frame numbers, to count as in non-drop-frame
+        let mut frame = self.frame;
+        if dropped > 0 {
+            let frames_per_10_minutes = nominal * 600 - dropped * 9;
+            let frames_per_minute = nominal * 60 - dropped;
+            let tens = frame / frames_per_10_minutes;
+            let remainder = frame % frames_per_10_minutes;
+
+            frame += dropped * 9 * tens;
+            if remainder > dropped {
+                frame += dropped * ((remainder - dropped) / frames_per_minute);
+            }
+        }
+
+        let frames = frame % nominal;
+        let seconds = frame / nominal;
+
+        (
+            (seconds / 3600) as u32,
+            (seconds / 60 % 60) as u32,
+            (seconds % 60) as u32,
+            frames as u32,
+        )
+    }
+
+    /// Returns the timecode `frames` later, or None on overflow
+    pub fn checked_add(self, frames: u64) -> Option<Self> {
+        Some(Self {
+            frame: self.frame.checked_add(frames)?,
+            ..self
+        })
+    }
+
+    /// Returns the timecode `frames` earlier, or None if it would be before
+    /// 00:00:00:00
+    pub fn checked_sub(self, frames: u64) -> Option<Self> {
+        Some(Self {
+            frame: self.frame.checked_sub(frames)?,
+            ..self
+        })
+    }
+}
+
+/// Number of frame numbers per second, ie 30 for 29.97 fps
+fn nominal_fps(frame_rate: Rational) -> eyre::Result<u64> {
+    if frame_rate.num() <= 0 {
+        eyre::bail!("invalid timecode frame rate {}", frame_rate);
+    }
+
+    Ok((frame_rate.num() as u64).div_ceil(frame_rate.den() as u64))
+}
+
+/// Number of frame numbers skipped each minute
+fn dropped_frames(frame_rate: Rational, drop_frame: bool) -> eyre::Result<u64> {
+    if !drop_frame {
+        return Ok(0);
+    }
+
+    match (frame_rate.num(), frame_rate.den()) {
+        (30000, 1001) => Ok(2),
+        (60000, 1001) => Ok(4),
+        _ => eyre::bail!("drop-frame is not supported at {} fps", frame_rate),
+    }
+}
+
+/// Serialized as its string representation
+impl serde::Serialize for Timecode {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl Display for Timecode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (hours, minutes, seconds, frames) = self.components();
+        let separator = if self.drop_frame { ';' } else { ':' };
+
+        write!(
+            f,
+            "{:02}:{:02}:{:02}{}{:02}",
+            hours, minutes, seconds, separator, frames
+        )
+    }
+}
+
+impl Add<u64> for Timecode {
+    type Output = Self;
+
+    fn add(self, frames: u64) -> Self::Output {
+        self.checked_add(frames).expect("timecode overflow")
+    }
+}
+
+impl Sub<u64> for Timecode {
+    type Output = Self;
+
+    fn sub(self, frames: u64) -> Self::Output {
+        self.checked_sub(frames)
+            .expect("timecode cannot be before 00:00:00:00")
+    }
+}
+
+/// Adds a duration expressed as a timecode. Panics if the frame rates differ.
+impl Add for Timecode {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self::Output {
+        assert_eq!(
+            self.frame_rate, other.frame_rate,
+            "timecode frame rates differ"
+        );
+        self + other.frame
+    }
+}
+
+/// Returns the duration between two timecodes, as a timecode.
+/// Panics if the frame rates differ, or if `other` is later.
+impl Sub for Timecode {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self::Output {
+        assert_eq!(
+            self.frame_rate, other.frame_rate,
+            "timecode frame rates differ"
+        );
+        self - other.frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NTSC: Rational = Rational::new(30000, 1001);
+
+    #[test]
+    fn non_drop_frame() {
+        let rate = Rational::new(25, 1);
+        let timecode = Timecode::parse("01:00:00:00", rate).unwrap();
+        assert_eq!(timecode.frame(), 90_000);
+        assert_eq!(timecode.to_duration(), Duration::from_secs(3600));
+        assert_eq!((timecode + 24).to_string(), "01:00:00:24");
+        assert_eq!((timecode + 25).to_string(), "01:00:01:00");
+        assert_eq!((timecode - 1).to_string(), "00:59:59:24");
+
+        assert!(Timecode::parse("00:00:00:25", rate).is_err());
+        assert!(Timecode::parse("00:60:00:00", rate).is_err());
+        assert!(Timecode::parse("00:00:00", rate).is_err());
+        assert!(Timecode::parse("00:00:00;00", rate).is_err());
+    }
+
+    #[test]
+    fn drop_frame() {
+        let timecode = Timecode::parse("00:00:59;29", NTSC).unwrap();
+        assert_eq!(timecode.frame(), 1799);
+        assert_eq!((timecode + 1).to_string(), "00:01:00;02");
+        assert!(Timecode::parse("00:01:00;00", NTSC).is_err());
+        assert!(Timecode::parse("00:01:00;01", NTSC).is_err());
+
+        // Every tenth minute is not dropped
+        let timecode = Timecode::parse("00:09:59;29", NTSC).unwrap();
+        assert_eq!(timecode.frame(), 17981);
+        assert_eq!((timecode + 1).to_string(), "00:10:00;00");
+
+        // One hour of drop-frame timecode is (almost) one hour
+        let timecode = Timecode::parse("01:00:00;00", NTSC).unwrap();
+        assert_eq!(timecode.frame(), 107_892);
+        assert_eq!(
+            timecode.to_duration(),
+            Duration::from_nanos(3_599_996_400_000)
+        );
+
+        for frame in (0..300_000).step_by(7) {
+            let timecode = Timecode::from_frame(frame, NTSC, true).unwrap();
+            let parsed = Timecode::parse(&timecode.to_string(), NTSC).unwrap();
+            assert_eq!(parsed.frame(), frame);
+        }
+
+        assert!(Timecode::parse("00:00:00;00", Rational::new(25, 1)).is_err());
+    }
+
+    #[test]
+    fn arithmetic() {
+        let start = Timecode::parse("00:59:58:00", NTSC).unwrap();
+        let end = Timecode::parse("01:00:02:12", NTSC).unwrap();
+        assert_eq!((end - start).frame(), 132);
+        assert_eq!((start + (end - start)), end);
+        assert_eq!(start.checked_sub(200_000), None);
+
+        let timecode = Timecode::from_duration(Duration::from_secs(10), NTSC, false).unwrap();
+        assert_eq!(timecode.to_string(), "00:00:09:29");
+    }
+}
@@ -0,0 +1,134 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::process::Command;
+
+fn movieru(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_movieru"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn probe() {
+    let Some(fixtures) = common::Fixtures::new() else {
+        return;
+    };
+    let testsrc = fixtures.testsrc();
+
+    let output = movieru(&["probe", "--json", testsrc.to_str().unwrap()]);
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["video_streams"][0]["width"], 320);
+    assert_eq!(json["video_streams"][0]["frame_rate"], "25/1");
+
+    let output = movieru(&["probe", testsrc.to_str().unwrap()]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("video h264 320x240"));
+
+    // Without any video stream
+    let audio = fixtures.lavfi(
+        "sine.m4a",
+        &["sine=frequency=440:sample_rate=48000:duration=1"],
+        &["-c:a", "aac"],
+    );
+    let output = movieru(&["probe", "--json", audio.to_str().unwrap()]);
+    assert!(output.status.success(), "{:?}", output);
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["video_streams"].as_array().unwrap().len(), 0);
+    assert_eq!(json["audio_streams"][0]["sample_rate"], 48000);
+}
+
+#[test]
+fn concat() {
+    let Some(fixtures) = common::Fixtures::new() else {
+        return;
+    };
+    let testsrc = fixtures.testsrc();
+    let concat = fixtures.path("concat.y4m");
+    let output = movieru(&[
+        "concat",
+        testsrc.to_str().unwrap(),
+        testsrc.to_str().unwrap(),
+        "-o",
+        concat.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    let y4m = std::fs::read(&concat).unwrap();
+    assert_eq!(
+        y4m.windows(6).filter(|window| window == b"FRAME\n").count(),
+        100
+    );
+
+    // Frame rates must match
+    let numbered = fixtures.numbered();
+    let output = movieru(&[
+        "concat",
+        testsrc.to_str().unwrap(),
+        numbered.to_str().unwrap(),
+        "-o",
+        concat.to_str().unwrap(),
+    ]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("fps"));
+}
+
+#[test]
+fn cut_and_render() {
+    let Some(fixtures) = common::Fixtures::new() else {
+        return;
+    };
+    let testsrc = fixtures.testsrc();
+    let cut = fixtures.path("cut.y4m");
+    let output = movieru(&[
+        "cut",
+        testsrc.to_str().unwrap(),
+        "-o",
+        cut.to_str().unwrap(),
+        "--start",
+        "0.4s",
+        "--duration",
+        "10",
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    let y4m = std::fs::read(&cut).unwrap();
+    assert_eq!(
+        y4m.windows(6).filter(|window| window == b"FRAME\n").count(),
+        10
+    );
+
+    let pipeline = fixtures.path("pipeline.json");
+    std::fs::write(
+        &pipeline,
+        r#"{"effects": [{"resize": {"width": 32, "height": 24}}], "output": {"path": "unused.mp4"}}"#,
+    )
+    .unwrap();
+    let rendered = fixtures.path("rendered.y4m");
+    let output = movieru(&[
+        "render",
+        pipeline.to_str().unwrap(),
+        "-i",
+        testsrc.to_str().unwrap(),
+        "-o",
+        rendered.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(std::fs::read(&rendered)
+        .unwrap()
+        .starts_with(b"YUV4MPEG2 W32 H24 F25:1"));
+
+    // Invalid pipelines are rejected before reading any frame
+    std::fs::write(
+        &pipeline,
+        r#"{"effects": [{"crop": {"x": 0, "y": 0, "width": 1000, "height": 10}}], "output": {"path": "unused.mp4"}}"#,
+    )
+    .unwrap();
+    let output = movieru(&[
+        "render",
+        pipeline.to_str().unwrap(),
+        "-i",
+        testsrc.to_str().unwrap(),
+    ]);
+    assert!(!output.status.success());
+}
@@ -0,0 +1,128 @@
+//! Fixture media generated with FFMpeg's `lavfi` sources, in a temporary
+//! directory removed at the end of each test.
+//! The tests using them are skipped when FFMpeg is not installed, unless
+//! `MOVIERU_REQUIRE_FFMPEG` is set, ie in CI.
+#![allow(dead_code)]
+
+use std::{
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+use tempfile::TempDir;
+
+pub struct Fixtures {
+    dir: TempDir,
+}
+
+impl Fixtures {
+    /// Returns None, so that the test is skipped, when FFMpeg cannot be run.
+    /// Panics instead when `MOVIERU_REQUIRE_FFMPEG` is set.
+    pub fn new() -> Option<Self> {
+        let available = Command::new("ffmpeg")
+            .arg("-version")
+            .output()
+            .is_ok_and(|output| output.status.success() && !output.stdout.is_empty());
+        if !available {
+            assert!(
+                std::env::var_os("MOVIERU_REQUIRE_FFMPEG").is_none(),
+                "ffmpeg is required by this test (MOVIERU_REQUIRE_FFMPEG is set)"
+            );
+            eprintln!("ffmpeg is not available, skipping (set MOVIERU_REQUIRE_FFMPEG to fail)");
+            return None;
+        }
+
+        Some(Self {
+            dir: tempfile::tempdir().expect("cannot create temporary directory"),
+        })
+    }
+
+    /// Returns the path of a file in the fixtures directory
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.path().join(name)
+    }
+
+    /// Generates `name` from `lavfi` inputs, encoded with the given arguments
+    pub fn lavfi(&self, name: &str, inputs: &[&str], args: &[&str]) -> PathBuf {
+        let path = self.path(name);
+        let mut command = Command::new("ffmpeg");
+        command.args(["-y", "-loglevel", "error"]);
+        for input in inputs {
+            command.args(["-f", "lavfi", "-i", input]);
+        }
+        let output = command
+            .args(args)
+            .arg(&path)
+            .stdin(Stdio::null())
+            .output()
+            .expect("cannot run ffmpeg");
+        assert!(
+            output.status.success(),
+            "cannot generate {}: {}",
+            name,
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        path
+    }
+
+    /// 2 seconds of `testsrc` at 320x240 and 25 fps, with a 440 Hz sine
+    pub fn testsrc(&self) -> PathBuf {
+        self.lavfi(
+            "testsrc.mp4",
+            &[
+                "testsrc=size=320x240:rate=25:duration=2",
+                "sine=frequency=440:sample_rate=48000:duration=2",
+            ],
+            &["-c:v", "libx264", "-pix_fmt", "yuv420p", "-c:a", "aac"],
+        )
+    }
+
+    /// Lossless 29.97 fps video of 100 frames, where the index of each frame
+    /// can be read back with `frame_index`
+    pub fn numbered(&self) -> PathBuf {
+        self.lavfi(
+            "numbered.mp4",
+            &["color=c=black:s=32x16:r=30000/1001"],
+            &[
+                "-vf",
+                "format=rgb24,geq=r='8*mod(N,25)':g='8*floor(N/25)':b=0",
+                "-frames:v",
+                "100",
+                "-c:v",
+                "libx264rgb",
+                "-qp",
+                "0",
+                "-g",
+                "12",
+            ],
+        )
+    }
+
+    /// Lossless 64x48 video of 25 frames of a single color
+    pub fn color(&self, color: &str) -> PathBuf {
+        self.lavfi(
+            &format!("{}.mp4", color),
+            &[&format!("color=c={}:s=64x48:r=25:d=1", color)],
+            &["-c:v", "libx264rgb", "-qp", "0"],
+        )
+    }
+}
+
+/// Reads back the index of a `Fixtures::numbered` frame, from its first
+/// pixel
+pub fn frame_index(raw: &[u8]) -> u64 {
+    raw[0] as u64 / 8 + raw[1] as u64 / 8 * 25
+}
+
+/// Peak signal-to-noise ratio between two 8 bits images, in dB
+pub fn psnr(expected: &[u8], actual: &[u8]) -> f64 {
+    assert_eq!(expected.len(), actual.len());
+    let mse = expected
+        .iter()
+        .zip(actual)
+        .map(|(&a, &b)| (a as f64 - b as f64).powi(2))
+        .sum::<f64>()
+        / expected.len() as f64;
+
+    10.0 * (255.0 * 255.0 / mse).log10()
+}
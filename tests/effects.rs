@@ -0,0 +1,54 @@
+mod common;
+
+use image::{Luma, Rgb};
+use movieru::{Clip, EffectsExt, Frame};
+
+#[test]
+fn crop_resize_grayscale() {
+    let Some(fixtures) = common::Fixtures::new() else {
+        return;
+    };
+    let red = || Clip::<Rgb<u8>>::from_file(fixtures.color("red")).unwrap();
+
+    let frames = red().iter_frames().unwrap().collect::<Vec<_>>();
+    assert_eq!(frames.len(), 25);
+    assert!(frames
+        .iter()
+        .all(|frame| frame.as_raw() == [255, 0, 0].repeat(64 * 48)));
+
+    let cropped = red()
+        .iter_frames()
+        .unwrap()
+        .crop(8, 4, 16, 10)
+        .collect::<Vec<_>>();
+    assert_eq!(cropped.len(), 25);
+    assert_eq!(cropped[0].as_raw(), &[255, 0, 0].repeat(16 * 10));
+
+    let resized = red()
+        .iter_frames()
+        .unwrap()
+        .resize(20, 30)
+        .collect::<Vec<_>>();
+    assert_eq!(resized[24].as_raw(), &[255, 0, 0].repeat(20 * 30));
+
+    // BT.601 luma of pure red
+    let gray: Vec<Frame<Luma<u8>>> = red()
+        .iter_frames()
+        .unwrap()
+        .crop(0, 0, 32, 48)
+        .resize(16, 16)
+        .grayscale()
+        .collect();
+    assert_eq!(gray.len(), 25);
+    assert!(gray.iter().all(|frame| frame.as_raw() == vec![76; 16 * 16]));
+}
+
+#[test]
+fn read_as_gray() {
+    let Some(fixtures) = common::Fixtures::new() else {
+        return;
+    };
+    let white = Clip::<Luma<u8>>::from_file(fixtures.color("white")).unwrap();
+    let frame = white.frame_at_index(12).unwrap();
+    assert_eq!(frame.as_raw(), &vec![255; 64 * 48]);
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use image::Rgb;
+use movieru::{
+    Clip, FFMpegVideoWriter, FrameSink, Generator, ImageSequenceWriter, Pattern, Rational,
+};
+use std::path::{Path, PathBuf};
+
+const RATE: Rational = Rational::new(25, 1);
+
+fn frames() -> Generator<Rgb<u8>> {
+    Generator::new(Pattern::ColorBars, (64, 48), RATE, 5)
+}
+
+/// Encodes a video at `path` and reads it back
+fn roundtrip(path: &Path) {
+    FFMpegVideoWriter::to_file(path, (64, 48), RATE, "rgb24")
+        .unwrap()
+        .write_all(frames())
+        .unwrap();
+    assert!(path.is_file(), "{:?} was not written", path);
+
+    let clip = Clip::<Rgb<u8>>::from_file(path).unwrap();
+    assert_eq!(clip.dimensions(), (64, 48));
+    assert_eq!(clip.iter_frames().unwrap().count(), 5, "{:?}", path);
+}
+
+#[test]
+fn odd_filenames() {
+    let Some(fixtures) = common::Fixtures::new() else {
+        return;
+    };
+
+    let mut paths = vec![
+        fixtures.path("spaces and 'quotes'.mp4"),
+        fixtures.path("12:30 take.mp4"),
+        fixtures.path("http:not a url.mp4"),
+        fixtures.path("café ☕.mp4"),
+    ];
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        // Latin-1 `é`, not valid UTF-8
+        paths.push(
+            fixtures
+                .path("")
+                .join(std::ffi::OsStr::from_bytes(b"caf\xe9.mp4")),
+        );
+    }
+    for path in &paths {
+        roundtrip(path);
+    }
+
+    // Relative paths starting with a dash are not taken as options, written
+    // and read back by the CLI run from the fixtures directory
+    #[cfg(feature = "cli")]
+    {
+        let movieru = |args: &[&std::ffi::OsStr]| {
+            let output = std::process::Command::new(env!("CARGO_BIN_EXE_movieru"))
+                .args(args)
+                .current_dir(fixtures.path(""))
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{:?}", output);
+            String::from_utf8_lossy(&output.stdout).into_owned()
+        };
+        movieru(&[
+            "cut".as_ref(),
+            paths[0].as_ref(),
+            "--output=-dash.mp4".as_ref(),
+        ]);
+        assert!(fixtures.path("-dash.mp4").is_file());
+        let probe = movieru(&["probe".as_ref(), "--".as_ref(), "-dash.mp4".as_ref()]);
+        assert!(probe.contains("64x48"), "{}", probe);
+    }
+
+    // Image sequences in a directory with a colon
+    let pattern: PathBuf = fixtures.path("take:1").join("frame_%03d.png");
+    std::fs::create_dir_all(pattern.parent().unwrap()).unwrap();
+    ImageSequenceWriter::to_pattern(&pattern, 1)
+        .unwrap()
+        .write_all(frames())
+        .unwrap();
+    let clip = Clip::<Rgb<u8>>::from_image_sequence(&pattern, RATE).unwrap();
+    assert_eq!(clip.iter_frames().unwrap().count(), 5);
+}
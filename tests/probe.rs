@@ -0,0 +1,54 @@
+mod common;
+
+use image::Rgb;
+use movieru::{Clip, PixelFormat, Rational};
+use std::time::Duration;
+
+#[test]
+fn probe_testsrc() {
+    let Some(fixtures) = common::Fixtures::new() else {
+        return;
+    };
+    let clip = Clip::<Rgb<u8>>::from_file(fixtures.testsrc()).unwrap();
+
+    assert_eq!(clip.dimensions(), (320, 240));
+    assert_eq!(clip.frame_rate(), Rational::new(25, 1));
+    assert_eq!(clip.pixel_format(), &PixelFormat::Yuv420p);
+    assert_eq!(clip.nb_frames(), 50);
+    assert_eq!(clip.len(), 50);
+    assert!(!clip.has_alpha());
+    assert!(!clip.is_variable_frame_rate());
+    assert!(clip.duration().abs_diff(Duration::from_secs(2)) < Duration::from_millis(50));
+
+    let metadata = clip.metadata();
+    assert!(metadata.format.format_name.contains("mp4"));
+    let video = metadata.video_stream().unwrap();
+    assert_eq!(video.codec_name, "h264");
+    assert_eq!((video.width, video.height), (320, 240));
+    let audio = metadata.audio_stream().unwrap();
+    assert_eq!(audio.codec_name, "aac");
+    assert_eq!(audio.sample_rate, 48000);
+    assert_eq!(audio.channels, 1);
+}
+
+#[test]
+fn frame_count() {
+    let Some(fixtures) = common::Fixtures::new() else {
+        return;
+    };
+    let clip = Clip::<Rgb<u8>>::from_file(fixtures.testsrc()).unwrap();
+    let frames = clip.iter_frames().unwrap();
+    assert_eq!(frames.size_hint(), (50, Some(50)));
+
+    let mut count = 0;
+    for frame in frames {
+        assert_eq!(frame.as_raw().len(), 320 * 240 * 3);
+        count += 1;
+    }
+    assert_eq!(count, 50);
+}
+
+#[test]
+fn missing_file() {
+    assert!(Clip::<Rgb<u8>>::from_file("does/not/exist.mp4").is_err());
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use common::psnr;
+use image::{Luma, Rgb};
+use movieru::{
+    Clip, Color, FFMpegVideoWriter, FrameSink, Generator, ImageSequenceWriter, Pattern, Rational,
+    Y4mWriter,
+};
+
+const RATE: Rational = Rational::new(25, 1);
+
+fn gradient() -> Pattern {
+    Pattern::LinearGradient {
+        from: Color::rgb(20, 40, 200),
+        to: Color::rgb(240, 200, 30),
+        angle: 30.0,
+    }
+}
+
+#[test]
+fn encoded_video() {
+    let Some(fixtures) = common::Fixtures::new() else {
+        return;
+    };
+    let path = fixtures.path("roundtrip.mp4");
+    for pattern in [gradient(), Pattern::ColorBars] {
+        let frames = Generator::<Rgb<u8>>::new(pattern, (64, 48), RATE, 25);
+        let mut writer = FFMpegVideoWriter::to_file(&path, (64, 48), RATE, "rgb24").unwrap();
+        writer.write_all(frames.clone()).unwrap();
+
+        let clip = Clip::<Rgb<u8>>::from_file(&path).unwrap();
+        assert_eq!((clip.len(), clip.dimensions()), (25, (64, 48)));
+        for (expected, actual) in frames.zip(clip.iter_frames().unwrap()) {
+            let psnr = psnr(expected.as_raw(), actual.as_raw());
+            assert!(psnr > 30.0, "PSNR is {:.1} dB", psnr);
+        }
+    }
+}
+
+#[test]
+fn lossless_outputs() {
+    let Some(fixtures) = common::Fixtures::new() else {
+        return;
+    };
+    let noise = Pattern::Noise {
+        seed: 7,
+        gray: false,
+    };
+
+    // Image sequence
+    let frames = Generator::<Rgb<u8>>::new(noise.clone(), (32, 24), RATE, 10);
+    ImageSequenceWriter::to_dir(
+        fixtures.path("png"),
+        "frame_",
+        3,
+        1,
+        image::ImageFormat::Png,
+    )
+    .unwrap()
+    .write_all(frames.clone())
+    .unwrap();
+    let clip =
+        Clip::<Rgb<u8>>::from_image_sequence(fixtures.path("png/frame_%03d.png"), RATE).unwrap();
+    assert_eq!(clip.len(), 10);
+    for (expected, actual) in frames.zip(clip.iter_frames().unwrap()) {
+        assert_eq!(expected.as_raw(), actual.as_raw());
+    }
+
+    // Gray Y4M stream
+    let path = fixtures.path("noise.y4m");
+    let frames = Generator::<Luma<u8>>::new(noise, (32, 24), RATE, 10);
+    Y4mWriter::to_file(&path, (32, 24), RATE)
+        .unwrap()
+        .write_all(frames.clone())
+        .unwrap();
+    let clip = Clip::<Luma<u8>>::from_file(&path).unwrap();
+    assert_eq!(clip.len(), 10);
+    for (expected, actual) in frames.zip(clip.iter_frames().unwrap()) {
+        assert_eq!(expected.as_raw(), actual.as_raw());
+    }
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use image::Rgb;
+use movieru::{Clip, FFMpegVideoWriter, Frame, FrameSink, Generator, Pattern, Rational};
+use std::time::Duration;
+
+/// 2 seconds of `testsrc` in Matroska, which can be read from a pipe
+fn matroska(fixtures: &common::Fixtures) -> std::path::PathBuf {
+    fixtures.lavfi(
+        "testsrc.mkv",
+        &["testsrc=size=160x120:rate=25:duration=2"],
+        &["-c:v", "libx264", "-pix_fmt", "yuv420p"],
+    )
+}
+
+#[test]
+fn bytes_and_reader() {
+    let Some(fixtures) = common::Fixtures::new() else {
+        return;
+    };
+    let path = matroska(&fixtures);
+    let file = Clip::<Rgb<u8>>::from_file(&path).unwrap();
+
+    let clip = Clip::<Rgb<u8>>::from_bytes(std::fs::read(&path).unwrap()).unwrap();
+    assert_eq!(clip.dimensions(), (160, 120));
+    assert_eq!(clip.len(), file.len());
+    for (expected, actual) in file
+        .clone()
+        .iter_frames()
+        .unwrap()
+        .zip(clip.clone().iter_frames().unwrap())
+    {
+        assert_eq!(expected.as_raw(), actual.as_raw());
+    }
+    // Frames are decoded from the start, then dropped
+    assert_eq!(
+        clip.frame_at_index(30).unwrap().as_raw(),
+        file.frame_at_index(30).unwrap().as_raw()
+    );
+
+    // Streamed, it can only be read once
+    let clip = Clip::<Rgb<u8>>::from_reader(std::fs::File::open(&path).unwrap()).unwrap();
+    assert_eq!(clip.len(), file.len());
+    assert_eq!(clip.clone().iter_frames().unwrap().count(), file.len());
+    assert!(clip.iter_frames().is_err());
+
+    let file = std::fs::File::open(&path).unwrap();
+    let clip = Clip::<Rgb<u8>>::from_reader_buffered(file).unwrap();
+    assert_eq!(clip.clone().iter_frames().unwrap().count(), 50);
+    assert_eq!(clip.iter_frames().unwrap().count(), 50);
+}
+
+#[cfg(unix)]
+#[test]
+fn named_pipe() {
+    let Some(fixtures) = common::Fixtures::new() else {
+        return;
+    };
+    let path = matroska(&fixtures);
+    let pipe = fixtures.path("pipe");
+    let status = std::process::Command::new("mkfifo")
+        .arg(&pipe)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let writer = {
+        let pipe = pipe.clone();
+        std::thread::spawn(move || std::fs::write(pipe, std::fs::read(path).unwrap()))
+    };
+    let clip = Clip::<Rgb<u8>>::from_file(&pipe).unwrap();
+    writer.join().unwrap().unwrap();
+    assert_eq!(clip.iter_frames().unwrap().count(), 50);
+}
+
+#[test]
+fn writer() {
+    let Some(fixtures) = common::Fixtures::new() else {
+        return;
+    };
+    let rate = Rational::new(25, 1);
+    for (name, format) in [("fragmented.mp4", "mp4"), ("stream.ts", "mpegts")] {
+        let path = fixtures.path(name);
+        let file = std::fs::File::create(&path).unwrap();
+        let mut writer =
+            FFMpegVideoWriter::to_writer(file, format, (64, 48), rate, "rgb24").unwrap();
+        writer
+            .write_all(Generator::<Rgb<u8>>::new(
+                Pattern::ColorBars,
+                (64, 48),
+                rate,
+                25,
+            ))
+            .unwrap();
+
+        assert_eq!(
+            writer.bytes_written(),
+            Some(std::fs::metadata(&path).unwrap().len())
+        );
+        let clip = Clip::<Rgb<u8>>::from_file(&path).unwrap();
+        assert_eq!(clip.dimensions(), (64, 48));
+        assert_eq!(clip.iter_frames().unwrap().count(), 25);
+    }
+}
+
+#[test]
+fn image_sequence() {
+    let Some(fixtures) = common::Fixtures::new() else {
+        return;
+    };
+    // The red channel is 8 times the index of the image
+    let red = |clip: Clip<Rgb<u8>>| -> Vec<u8> {
+        clip.iter_frames()
+            .unwrap()
+            .map(|frame| frame.as_raw()[0] / 8)
+            .collect()
+    };
+    for index in 0..10u8 {
+        Frame::<Rgb<u8>>::from_vec([8 * index, 0, 0].repeat(8 * 4), (8, 4))
+            .save(fixtures.path(&format!("frame_{:04}.png", index + 1)))
+            .unwrap();
+    }
+
+    let rate = Rational::new(24, 1);
+    let clip = Clip::<Rgb<u8>>::from_image_sequence(fixtures.path("frame_%04d.png"), rate).unwrap();
+    assert_eq!((clip.len(), clip.dimensions()), (10, (8, 4)));
+    assert_eq!(clip.duration(), Duration::from_nanos(416_666_667));
+    assert_eq!(red(clip.clone()), (0..10).collect::<Vec<_>>());
+    assert_eq!(red(clip.subclip(4, 3).unwrap()), vec![4, 5, 6]);
+
+    let image = fixtures.path("frame_0004.png");
+    let still = Clip::<Rgb<u8>>::from_still(&image, Duration::from_secs(2), rate).unwrap();
+    assert_eq!(still.len(), 48);
+    assert_eq!(red(still.subclip(40, 8).unwrap()), vec![3; 8]);
+    assert!(Clip::<Rgb<u8>>::from_still(&image, Duration::ZERO, rate).is_err());
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use common::frame_index;
+use image::Rgb;
+use movieru::{Clip, Timecode};
+use std::time::Duration;
+
+fn indexes(clip: Clip<Rgb<u8>>) -> Vec<u64> {
+    clip.iter_frames()
+        .unwrap()
+        .map(|frame| frame_index(frame.as_raw()))
+        .collect()
+}
+
+#[test]
+fn boundaries() {
+    let Some(fixtures) = common::Fixtures::new() else {
+        return;
+    };
+    let clip = Clip::<Rgb<u8>>::from_file(fixtures.numbered()).unwrap();
+    assert_eq!(indexes(clip.clone()), (0..100).collect::<Vec<_>>());
+
+    // The end is excluded, adjacent ranges are contiguous
+    let first = clip.subclip_range(0, 37).unwrap();
+    let second = clip.subclip_range(37, 100).unwrap();
+    assert_eq!(indexes(first), (0..37).collect::<Vec<_>>());
+    assert_eq!(indexes(second), (37..100).collect::<Vec<_>>());
+
+    // Not starting on a keyframe
+    assert_eq!(
+        indexes(clip.subclip(37, 20).unwrap()),
+        (37..57).collect::<Vec<_>>()
+    );
+
+    // Times at 29.97 fps
+    let second = clip
+        .subclip_for(Duration::from_secs(1), Duration::from_secs(1))
+        .unwrap();
+    assert_eq!(indexes(second), (29..59).collect::<Vec<_>>());
+
+    // Sub-second precision
+    let subclip = clip
+        .subclip(Duration::from_millis(2500), Duration::from_millis(100))
+        .unwrap();
+    assert_eq!(indexes(subclip), vec![74, 75, 76]);
+
+    // Timecodes, relative to 00:00:00:00 without a timecode in the file
+    let rate = clip.frame_rate();
+    let start = Timecode::parse("00:00:02;00", rate).unwrap();
+    let end = Timecode::parse("00:00:02;10", rate).unwrap();
+    assert_eq!(
+        indexes(clip.subclip_range(start, end).unwrap()),
+        (60..70).collect::<Vec<_>>()
+    );
+
+    // Last frames, and out of range
+    assert_eq!(indexes(clip.subclip(98, 2).unwrap()), vec![98, 99]);
+    assert!(clip.subclip(98, 3).is_err());
+    assert!(clip.subclip(Duration::from_secs(4), 1).is_err());
+}
+
+#[test]
+fn random_access() {
+    let Some(fixtures) = common::Fixtures::new() else {
+        return;
+    };
+    let clip = Clip::<Rgb<u8>>::from_file(fixtures.numbered()).unwrap();
+
+    for index in [0, 1, 11, 12, 13, 50, 99] {
+        let frame = clip.frame_at_index(index).unwrap();
+        assert_eq!(frame_index(frame.as_raw()), index);
+    }
+    let frame = clip.frame_at(Duration::from_millis(2500)).unwrap();
+    assert_eq!(frame_index(frame.as_raw()), 74);
+    assert!(clip.frame_at_index(100).is_err());
+    let nested = clip.subclip(20, 40).unwrap().subclip(5, 10).unwrap();
+    assert_eq!(frame_index(nested.frame_at_index(0).unwrap().as_raw()), 25);
+
+    // Thumbnails are keyframes (every 12 frames)
+    let thumbnails = clip.clone().with_dimensions((16, 8)).thumbnails(4).unwrap();
+    assert_eq!(thumbnails.len(), 4);
+    for (time, thumbnail) in thumbnails {
+        assert_eq!(thumbnail.image().dimensions(), (16, 8));
+        let index = clip.frame_rate().frames_in(time);
+        assert_eq!(frame_index(thumbnail.as_raw()), index - index % 12);
+    }
+}